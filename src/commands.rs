@@ -1,8 +1,70 @@
+pub mod backup;
 pub mod decrypt;
+pub mod doctor;
 pub mod encrypt;
 pub mod export;
+pub mod history;
 pub mod init;
 pub mod list;
 pub mod migrate;
+pub mod passwd;
 pub mod remove;
 pub mod renew;
+pub mod rotate;
+pub mod transfer;
+pub mod trash;
+pub mod upgrade;
+pub mod vault;
+
+use crate::locker::{Locker, LockerEvent};
+use crate::utils::interaction::interaction;
+use crate::utils::keyfile::{read_key_file, StoredKey};
+use crate::utils::passphrase::passphrase_source;
+use crate::utils::secret::SecretString;
+use crate::utils::toolbox::get_locker_dir;
+use crate::LockerResult;
+use colored::Colorize;
+use std::path::Path;
+
+/// Opens the locker of the current user (see [`get_locker_dir`]) and reports
+/// its events on stderr, so they never mix with a secret written to stdout.
+pub(crate) fn open_locker() -> LockerResult<Locker> {
+    Ok(report_events(Locker::open(
+        get_locker_dir()?,
+        &passphrase_source()?,
+        interaction(),
+    )?))
+}
+
+/// Opens the locker in `dir` with a passphrase already read, and reports its events.
+///
+/// A key still stored in clear text is opened with [`Locker::open`] instead,
+/// so the passphrase protecting it from now on is chosen and confirmed.
+pub(crate) fn open_locker_with_passphrase(
+    dir: &Path,
+    passphrase: &SecretString,
+) -> LockerResult<Locker> {
+    let locker = match read_key_file(dir)? {
+        StoredKey::Legacy(_) => Locker::open(dir, &passphrase_source()?, interaction())?,
+        StoredKey::Wrapped(_) => Locker::open_with_passphrase(dir, passphrase)?,
+    };
+    Ok(report_events(locker))
+}
+
+/// Reports the events of a locker on stderr, like [`open_locker`].
+pub(crate) fn report_events(locker: Locker) -> Locker {
+    locker.with_observer(|event| match event {
+        LockerEvent::LegacyMetadataSealed => eprintln!(
+            "{}",
+            "🔒 Metadata encrypted and secret file names hidden.".green()
+        ),
+        LockerEvent::SecretExpired(name) => eprintln!(
+            "{}",
+            format!("⚠️ The secret '{}' has expired.", name).yellow()
+        ),
+        LockerEvent::MetadataMigrated(name) => eprintln!(
+            "{}",
+            format!("Metadata migrated for secret '{}'.", name).blue()
+        ),
+    })
+}
@@ -0,0 +1,90 @@
+use crate::commands::{open_locker_with_passphrase, report_events};
+use crate::locker::{ConflictPolicy, Locker, RestoreReport};
+use crate::utils::keyfile::KEY_FILE;
+use crate::utils::passphrase::{
+    passphrase_source, read_passphrase, read_passphrase_env, PassphraseSource,
+};
+use crate::utils::toolbox::get_locker_dir;
+use crate::LockerResult;
+use crate::SmartLockerError;
+use std::path::Path;
+
+/// Writes an encrypted archive of the whole vault: key, configuration, index and every secret.
+///
+/// The archive is protected by the passphrase of the vault.
+///
+/// # Returns
+/// * `Ok(usize)` - The number of secrets backed up.
+/// * `Err(SmartLockerError)` - If the passphrase is wrong or the archive cannot be written.
+pub fn backup_vault(output: &Path) -> LockerResult<usize> {
+    let passphrase = read_passphrase("🔑 Passphrase: ")?;
+    report_events(Locker::open_with_passphrase(
+        get_locker_dir()?,
+        &passphrase,
+    )?)
+    .backup(output, &passphrase)
+}
+
+/// Restores a backup into the current vault.
+///
+/// A missing vault is recreated as it was when the backup was made. Otherwise
+/// the secrets of the backup are imported into the vault, and `on_conflict`
+/// decides what happens to those that already exist.
+///
+/// The backup passphrase is read from `backup_passphrase_env` if given, and the
+/// vault passphrase from the configured source. Otherwise one passphrase is read
+/// for both, and the vault passphrase is asked for on the terminal only if it
+/// differs from the backup's.
+///
+/// # Returns
+/// * `Ok(RestoreReport)` - The secrets restored, replaced and skipped.
+/// * `Err(SmartLockerError)` - If the archive is invalid or a secret already exists, or
+///   `RestoreInterrupted` with the secrets already imported if one fails to import.
+pub fn restore_vault(
+    input: &Path,
+    on_conflict: ConflictPolicy,
+    backup_passphrase_env: Option<&str>,
+) -> LockerResult<RestoreReport> {
+    let locker_dir = get_locker_dir()?;
+    let backup_passphrase = match backup_passphrase_env {
+        Some(var) => read_passphrase_env(var)?,
+        None => read_passphrase("🔑 Backup passphrase: ")?,
+    };
+
+    if !locker_dir.join(KEY_FILE).exists() {
+        let locker = Locker::restore_backup(&locker_dir, input, &backup_passphrase)?;
+        return Ok(RestoreReport {
+            restored: locker
+                .list()?
+                .into_iter()
+                .map(|secret| secret.name().to_string())
+                .collect(),
+            ..RestoreReport::default()
+        });
+    }
+
+    let locker = if backup_passphrase_env.is_some() {
+        open_locker_with_passphrase(&locker_dir, &read_passphrase("🔑 Vault passphrase: ")?)?
+    } else {
+        // Le vault peut avoir changé de passphrase depuis la sauvegarde
+        match open_locker_with_passphrase(&locker_dir, &backup_passphrase) {
+            Err(SmartLockerError::DecryptionError(_))
+                if matches!(passphrase_source()?, PassphraseSource::Prompt) =>
+            {
+                open_locker_with_passphrase(
+                    &locker_dir,
+                    &read_passphrase("🔑 Vault passphrase: ")?,
+                )?
+            }
+            Err(SmartLockerError::DecryptionError(e)) => {
+                return Err(SmartLockerError::DecryptionError(format!(
+                    "{} If the vault and the backup use different passphrases, \
+                     give the backup passphrase with --backup-passphrase-env.",
+                    e
+                )))
+            }
+            locker => locker?,
+        }
+    };
+    locker.import_backup(input, &backup_passphrase, on_conflict)
+}
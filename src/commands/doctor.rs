@@ -0,0 +1,78 @@
+use crate::commands::open_locker;
+use crate::utils::interaction::confirm;
+use crate::LockerResult;
+use colored::Colorize;
+
+pub const EXIT_HEALTHY: i32 = 0; // Aucun problème trouvé
+pub const EXIT_FIXED: i32 = 1; // Des problèmes ont été trouvés, et tous corrigés
+pub const EXIT_PROBLEMS: i32 = 2; // Des problèmes restent à corriger
+pub const EXIT_FAILED: i32 = 3; // Le locker n'a pas pu être vérifié
+
+/// Checks the integrity of the locker and prints the problems found with the plan to fix them.
+///
+/// With `fix`, the plan is applied once confirmed (`--yes` confirms it
+/// without asking), and the locker is checked again.
+///
+/// # Returns
+/// * `Ok(i32)` - The exit code: `EXIT_HEALTHY`, `EXIT_FIXED` or `EXIT_PROBLEMS`.
+/// * `Err(SmartLockerError)` - If the locker cannot be opened, checked or repaired.
+pub fn doctor(fix: bool) -> LockerResult<i32> {
+    let locker = open_locker()?;
+    let issues = locker.check()?;
+    if issues.is_empty() {
+        println!(
+            "{}",
+            format!("✅ No problem found in {:?}.", locker.path()).green()
+        );
+        return Ok(EXIT_HEALTHY);
+    }
+
+    println!(
+        "{}",
+        format!(
+            "⚠️ {} problem(s) found in {:?}:",
+            issues.len(),
+            locker.path()
+        )
+        .yellow()
+    );
+    for issue in &issues {
+        println!("  - {}", issue);
+    }
+
+    let plan: Vec<_> = issues
+        .iter()
+        .filter_map(|issue| issue.fix().map(|fix| (issue, fix)))
+        .collect();
+    if plan.is_empty() {
+        println!("No problem can be fixed automatically.");
+        return Ok(EXIT_PROBLEMS);
+    }
+    println!("{}", "Plan:".blue());
+    for (issue, fix) in &plan {
+        println!("  - {}: {}", issue, fix);
+    }
+    if !fix {
+        println!("Run `doctor --fix` to apply this plan.");
+        return Ok(EXIT_PROBLEMS);
+    }
+    if !confirm(&format!("Apply the {} fix(es) above?", plan.len()))? {
+        println!("No change made.");
+        return Ok(EXIT_PROBLEMS);
+    }
+
+    let fixed = locker.repair(&issues)?;
+    println!("{}", format!("✅ {} problem(s) fixed.", fixed).green());
+    let remaining = locker.check()?;
+    if remaining.is_empty() {
+        return Ok(EXIT_FIXED);
+    }
+    println!(
+        "{}",
+        format!("⚠️ {} problem(s) remain:", remaining.len()).yellow()
+    );
+    for issue in &remaining {
+        println!("  - {}", issue);
+    }
+    Ok(EXIT_PROBLEMS)
+}
@@ -1,4 +1,5 @@
-use crate::commands::list;
+use crate::commands::open_locker;
+use crate::utils::name::env_var_name;
 use crate::LockerResult;
 use crate::SmartLockerError;
 use std::env;
@@ -16,7 +17,8 @@ impl ExportFormat {
         for secret_name in secret_names {
             content.push_str(&format!(
                 "{}=$(smart-locker decrypt -n {})\n",
-                secret_name, secret_name
+                env_var_name(secret_name),
+                secret_name
             ));
         }
         fs::write(output_path, content).map_err(|e| {
@@ -37,18 +39,13 @@ pub fn export(format: &str, output_file: Option<&str>) -> LockerResult<()> {
         None => current_dir.join(".env"), // Valeur par défaut si aucun fichier n'est spécifié
     };
 
-    // Liste des secrets à exporter
-    let secret_list: Vec<String> = list::list_secrets_names()?;
-    if secret_list.is_empty() {
+    // Exporter les secrets
+    let count = open_locker()?.export(format, &output_path)?;
+    if count == 0 {
         eprintln!("Aucun secret à exporter.");
         return Ok(());
     }
 
-    match format {
-        "env" => ExportFormat::export_env_with_placeholders(&secret_list, &output_path)?,
-        _ => eprintln!("Format non supporté : {}", format),
-    }
-
     println!("Le fichier a été exporté à : {:?}", output_path);
     Ok(())
 }
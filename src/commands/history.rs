@@ -0,0 +1,39 @@
+use crate::commands::open_locker;
+use crate::LockerResult;
+use chrono::DateTime;
+use colored::Colorize;
+
+/// Describes the versions kept for a secret, the current one last.
+pub fn secret_history(name: &str) -> LockerResult<Vec<String>> {
+    let versions = open_locker()?.history(name)?;
+    let current = versions.last().map(|version| version.version());
+
+    Ok(versions
+        .iter()
+        .map(|version| {
+            let created_at = DateTime::from_timestamp(version.created_at() as i64, 0)
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| "Invalid timestamp".to_string());
+            format!(
+                "{}  Created At: {}  Type: {}{}",
+                format!("v{}", version.version()).blue(),
+                created_at,
+                version.value_type(),
+                if Some(version.version()) == current {
+                    "  (current)".green().to_string()
+                } else {
+                    String::new()
+                }
+            )
+        })
+        .collect())
+}
+
+/// Makes a previous version the current value of a secret again.
+///
+/// # Returns
+/// * `Ok(u32)` - The number of the new current version, holding the restored value.
+/// * `Err(SmartLockerError)` - If the secret or the version does not exist.
+pub fn rollback_secret(name: &str, to_version: u32) -> LockerResult<u32> {
+    open_locker()?.rollback(name, to_version)
+}
@@ -1,71 +1,93 @@
-use crate::utils::metadata::init_metadata_file;
-use crate::utils::toolbox::{ensure_dir_exists, get_locker_dir};
+use crate::utils::config::{Cipher, LockerConfig};
+use crate::utils::interaction::confirm;
+use crate::utils::kdf::{derive_key, remove_key_params, Kdf, KeyParams};
+use crate::utils::keyfile::{write_key_file, KeyFile, KEY_FILE};
+use crate::utils::lock::lock_locker;
+use crate::utils::metadata::{init_metadata_file, METADATA_INDEX_FILE};
+use crate::utils::passphrase::read_confirmed_passphrase;
+use crate::utils::secret::SecretBytes;
+use crate::utils::toolbox::{copy_atomic, create_private_dir, ensure_dir_exists, get_locker_dir};
 use crate::LockerResult;
 use crate::SmartLockerError;
 use colored::Colorize;
-use ring::pbkdf2;
-use std::fs;
-use std::num::NonZeroU32;
+use std::path::Path;
 
-pub fn init_locker_with_passphrase(passphrase: Option<&str>) -> Result<(), SmartLockerError> {
+pub fn init_locker_with_passphrase(passphrase: Option<&str>) -> Result<bool, SmartLockerError> {
+    init_locker_with_kdf(passphrase, Kdf::default())
+}
+
+/// Initialise le locker et protège sa clé par la passphrase avec le KDF choisi.
+///
+/// Retourne `false` si l'utilisateur a refusé de remplacer la clé existante :
+/// le locker n'a alors pas été modifié.
+pub fn init_locker_with_kdf(passphrase: Option<&str>, kdf: Kdf) -> Result<bool, SmartLockerError> {
     let locker_dir = get_locker_dir()?; // `?` propagates the error as a `Result`
 
     if !locker_dir.exists() {
-        fs::create_dir_all(&locker_dir).expect("Error creating folder ~/.locker");
+        create_private_dir(&locker_dir).expect("Error creating folder ~/.locker");
         println!("✅ Secure folder created: {:?}", locker_dir);
     }
 
-    let key_path = locker_dir.join("locker.key");
+    let key_path = locker_dir.join(KEY_FILE);
 
     if let Some(passphrase) = passphrase {
-        let salt = b"smartlocker_salt"; // Customize the salt
-        let new_key = derive_key_from_passphrase(passphrase, salt)?; // `?` propagates errors
-
+        let _lock = lock_locker(&locker_dir)?;
         if key_path.exists() {
             println!("🔑 A key already exists: {:?}", key_path);
             println!("⚠️ Warning: Generating a new key will replace the old one and make old secrets inaccessible.");
-            println!("Do you want to continue? (yes/no)");
-
-            let mut input = String::new();
-            std::io::stdin()
-                .read_line(&mut input)
-                .expect("Error reading user input");
-            if input.trim().to_lowercase() != "yes" {
+            if !confirm("Do you want to continue?")? {
                 println!("❌ Operation canceled.");
-                return Ok(()); // Return early with `Ok(())`
+                return Ok(false); // Return early with `Ok(false)`
             }
         }
 
-        fs::write(&key_path, new_key).expect("Error writing the key");
+        let data_key = write_wrapped_key(&locker_dir, passphrase, kdf)?;
         println!(
             "{}",
             format!(
-                "✅ New key generated from the passphrase and saved: {:?}",
+                "✅ New key generated, protected by the passphrase and saved: {:?}",
                 key_path
             )
             .green()
         );
+        // Initialiser l'index des métadonnées, chiffré avec la nouvelle clé
+        init_metadata_file(&data_key)?;
+        println!(
+            "✅ Metadata file initialized: {:?}",
+            locker_dir.join(METADATA_INDEX_FILE)
+        );
     } else {
-        init_locker()?; // Call another function that returns `Result`
+        init_locker_with_key_kdf(kdf)?; // Call another function that returns `Result`
     }
-    // Initialiser le fichier metadata.json
-    init_metadata_file().expect("Error initializing metadata file");
 
-    Ok(()) // Return success
+    Ok(true) // Return success
 }
 
 /// Initialise le répertoire `.locker` et génère une clé symétrique si nécessaire.
+///
+/// La clé est protégée par une passphrase lue depuis la source configurée
+/// (variable d'environnement, descripteur de fichier ou saisie).
 pub fn init_locker() -> LockerResult<()> {
+    init_locker_with_key_kdf(Kdf::default())
+}
+
+fn init_locker_with_key_kdf(kdf: Kdf) -> LockerResult<()> {
     let locker_dir = get_locker_dir()?;
-    ensure_dir_exists(&locker_dir)?;
+    if ensure_dir_exists(&locker_dir)? {
+        println!("✅ Directory created: {:?}", locker_dir);
+    }
 
-    let key_path = locker_dir.join("locker.key");
+    let _lock = lock_locker(&locker_dir)?;
+    let key_path = locker_dir.join(KEY_FILE);
     if !key_path.exists() {
-        let key = generate_key();
-        fs::write(&key_path, key).map_err(|e| {
-            SmartLockerError::FileSystemError(format!("Error writing the key: {}", e))
-        })?;
+        let passphrase = read_confirmed_passphrase("🔑 Choose a passphrase to protect the key: ")?;
+        let data_key = write_wrapped_key(&locker_dir, passphrase.expose(), kdf)?;
         println!("✅ Key generated and saved: {:?}", key_path);
+        init_metadata_file(&data_key)?;
+        println!(
+            "✅ Metadata file initialized: {:?}",
+            locker_dir.join(METADATA_INDEX_FILE)
+        );
     } else {
         println!("🔑 A key already exists: {:?}", key_path);
     }
@@ -73,6 +95,61 @@ pub fn init_locker() -> LockerResult<()> {
     Ok(())
 }
 
+/// Choisit l'algorithme de chiffrement des nouveaux secrets du locker.
+///
+/// Les secrets existants restent lisibles : l'algorithme est lu dans l'en-tête
+/// de chaque fichier `.slock`. `rotate-key` les rechiffre avec le nouvel algorithme.
+pub fn set_locker_cipher(cipher: Cipher) -> LockerResult<()> {
+    let locker_dir = get_locker_dir()?;
+    if ensure_dir_exists(&locker_dir)? {
+        println!("✅ Directory created: {:?}", locker_dir);
+    }
+
+    let _lock = lock_locker(&locker_dir)?;
+    let mut config = LockerConfig::load(&locker_dir)?;
+    config.cipher = cipher;
+    config.save(&locker_dir)?;
+    println!("✅ New secrets will be encrypted with {}.", cipher);
+    Ok(())
+}
+
+/// Choisit le nombre de versions précédentes gardées pour chaque secret.
+///
+/// Les versions en trop sont supprimées à la prochaine écriture de chaque secret.
+pub fn set_locker_keep_versions(keep_versions: usize) -> LockerResult<()> {
+    let locker_dir = get_locker_dir()?;
+    if ensure_dir_exists(&locker_dir)? {
+        println!("✅ Directory created: {:?}", locker_dir);
+    }
+
+    let _lock = lock_locker(&locker_dir)?;
+    let mut config = LockerConfig::load(&locker_dir)?;
+    config.keep_versions = keep_versions;
+    config.save(&locker_dir)?;
+    println!(
+        "✅ {} previous version(s) will be kept for each secret.",
+        keep_versions
+    );
+    Ok(())
+}
+
+/// Génère une nouvelle clé de données et l'écrit enveloppée par la passphrase.
+///
+/// Retourne la clé de données pour initialiser l'index des métadonnées.
+pub(crate) fn write_wrapped_key(
+    locker_dir: &Path,
+    passphrase: &str,
+    kdf: Kdf,
+) -> LockerResult<SecretBytes> {
+    let data_key = SecretBytes::new(generate_key());
+    let key_file = KeyFile::wrap(&data_key, passphrase, kdf)?;
+    write_key_file(locker_dir, &key_file)?;
+
+    // Les paramètres de dérivation sont désormais dans le fichier de clé
+    remove_key_params(locker_dir)?;
+    Ok(data_key)
+}
+
 /// Génère une clé symétrique aléatoire.
 pub fn generate_key() -> Vec<u8> {
     use rand::Rng;
@@ -82,32 +159,22 @@ pub fn generate_key() -> Vec<u8> {
     key.to_vec()
 }
 
-/// Generates a symmetric key from a passphrase and salt.
+/// Generates a symmetric key from a passphrase and the locker's key parameters.
 pub fn derive_key_from_passphrase(
     passphrase: &str,
-    salt: &[u8],
-) -> Result<Vec<u8>, SmartLockerError> {
+    params: &KeyParams,
+) -> Result<SecretBytes, SmartLockerError> {
     let locker_dir = get_locker_dir()?;
 
     // Check if the locker directory exists
     if !locker_dir.exists() {
-        fs::create_dir_all(&locker_dir).map_err(|e| {
+        create_private_dir(&locker_dir).map_err(|e| {
             SmartLockerError::FileSystemError(format!("Error creating folder ~/.locker: {}", e))
         })?;
         println!("✅ Secure folder created: {:?}", locker_dir);
     }
 
-    let mut key = [0u8; 32]; // 32-byte key
-    let iterations = NonZeroU32::new(100_000).unwrap(); // Number of PBKDF2 iterations
-    pbkdf2::derive(
-        pbkdf2::PBKDF2_HMAC_SHA256,
-        iterations,
-        salt,
-        passphrase.as_bytes(),
-        &mut key,
-    );
-
-    Ok(key.to_vec())
+    derive_key(passphrase, params)
 }
 
 /// Sauvegarde la clé de chiffrement.
@@ -117,9 +184,8 @@ pub fn backup_key() -> LockerResult<()> {
     let backup_path = locker_dir.join("locker.key.backup");
 
     if key_path.exists() {
-        fs::copy(&key_path, &backup_path).map_err(|e| {
-            SmartLockerError::FileSystemError(format!("Error backing up key: {}", e))
-        })?;
+        let _lock = lock_locker(&locker_dir)?;
+        copy_atomic(&key_path, &backup_path)?;
         println!("✅ Key backed up successfully: {:?}", backup_path);
     } else {
         println!("❌ No key to back up.");
@@ -134,9 +200,8 @@ pub fn restore_key() -> LockerResult<()> {
     let backup_path = locker_dir.join("locker.key.backup");
 
     if backup_path.exists() {
-        fs::copy(&backup_path, &key_path).map_err(|e| {
-            SmartLockerError::FileSystemError(format!("Error restoring key: {}", e))
-        })?;
+        let _lock = lock_locker(&locker_dir)?;
+        copy_atomic(&backup_path, &key_path)?;
         println!("✅ Key restored successfully: {:?}", key_path);
     } else {
         println!("❌ No backup key found.");
@@ -1,99 +1,50 @@
-use crate::commands::migrate::migrate_metadata;
-use crate::utils::metadata::{has_this_secret_metadata, read_metadata};
-use crate::utils::toolbox::{get_locker_dir, is_this_secret};
-use crate::MetadataFile;
+use crate::commands::open_locker;
+use crate::utils::interaction::confirm;
 use crate::SmartLockerError;
 use chrono::DateTime;
 use colored::Colorize;
-use std::fs;
-use std::io::{self, Write};
 
-pub fn list_secrets() -> Result<Vec<String>, SmartLockerError> {
-    let locker_dir = get_locker_dir()?;
+/// Describes the secrets whose name starts with `prefix`; an empty prefix lists them all.
+pub fn list_secrets(prefix: &str) -> Result<Vec<String>, SmartLockerError> {
+    let locker = open_locker()?;
     let mut secrets = Vec::new();
 
-    // Charger les métadonnées
-    let metadata_result = read_metadata();
-    let mut metadata = metadata_result.unwrap_or_else(|_| MetadataFile {
-        secrets: Default::default(),
-    });
-
-    let mut secrets_to_migrate = Vec::new();
-
-    // Rechercher tous les fichiers .slock
-    for entry in fs::read_dir(&locker_dir).map_err(|e| {
-        SmartLockerError::FileSystemError(format!("Error reading locker directory: {}", e))
-    })? {
-        let entry = entry.map_err(|e| {
-            SmartLockerError::FileSystemError(format!("Error reading directory entry: {}", e))
-        })?;
-        let path = entry.path();
-
-        // Vérifier si le fichier est un secret valide
-        let (is_valid, secret_name) = is_this_secret(&path, true);
-        if is_valid {
-            if let Some(secret_name) = secret_name {
-                // Vérifier les métadonnées
-                if !has_this_secret_metadata(&secret_name, &metadata) {
-                    secrets_to_migrate.push(secret_name.to_string());
-                }
-            }
-        }
-    }
-
-    // Proposer une migration si nécessaire
-    if !secrets_to_migrate.is_empty() {
-        println!(
-            "{}",
-            "⚠️ Some metadata are outdated or missing. Do you want to migrate them? (yes/no) : "
-                .green()
-        );
-
-        // Lire la saisie utilisateur
-        let mut input = String::new();
-        io::stdout().flush().unwrap();
-        io::stdin().read_line(&mut input).unwrap();
-        let input = input.trim().to_lowercase();
-
-        if input == "yes" {
-            for secret_name in secrets_to_migrate {
-                println!(
-                    "{}",
-                    format!("Migrating metadata for secret '{}'...", secret_name).blue()
-                );
-                migrate_metadata(Some(&secret_name))?; // Appel à migrate_metadata pour chaque secret
-            }
+    // Proposer une migration des fichiers .slock absents de l'index
+    if !locker.unindexed_secrets()?.is_empty() {
+        let question = "⚠️ Some metadata are outdated or missing. Do you want to migrate them?";
+        if confirm(&question.green().to_string())? {
+            locker.migrate(None)?;
             println!(
                 "{}",
                 "✅ Metadata migration completed successfully.".green()
             );
-            metadata = read_metadata()?; // Relire les métadonnées après migration
         } else {
             println!("{}", "⚠️ Migration skipped.".yellow());
         }
     }
 
     // Afficher les secrets
-    for (name, secret) in metadata.secrets.iter() {
-        let created_at = DateTime::from_timestamp(secret.created_at as i64, 0)
+    for secret in locker.list_prefix(prefix)? {
+        let created_at = DateTime::from_timestamp(secret.created_at() as i64, 0)
             .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
             .unwrap_or_else(|| "Invalid timestamp".to_string());
 
-        let expire_at = DateTime::from_timestamp(secret.expire_at as i64, 0)
+        let expire_at = DateTime::from_timestamp(secret.expire_at() as i64, 0)
             .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
             .unwrap_or_else(|| "Invalid timestamp".to_string());
 
         secrets.push(format!(
-            "{}\n  Created At: {}  Expire At: {}  Status: {}  Tags: {:?}",
-            name.blue(),
+            "{}\n  Created At: {}  Expire At: {}  Status: {}  Type: {}  Tags: {:?}",
+            secret.name().blue(),
             created_at,
             expire_at,
-            if secret.expired {
+            if secret.is_expired() {
                 "Expired".red().to_string()
             } else {
                 "Active".green().to_string()
             },
-            secret.tags
+            secret.value_type(),
+            secret.tags()
         ));
     }
 
@@ -105,10 +56,12 @@ pub fn list_secrets() -> Result<Vec<String>, SmartLockerError> {
 }
 
 pub fn list_secrets_names() -> Result<Vec<String>, SmartLockerError> {
-    let metadata = read_metadata()?;
-
     // Collecter uniquement les noms des secrets
-    let secret_names: Vec<String> = metadata.secrets.keys().cloned().collect();
+    let secret_names: Vec<String> = open_locker()?
+        .list()?
+        .iter()
+        .map(|secret| secret.name().to_string())
+        .collect();
 
     if secret_names.is_empty() {
         println!("⚠️ No secrets found.");
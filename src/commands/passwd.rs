@@ -0,0 +1,47 @@
+use crate::utils::kdf::{remove_key_params, Kdf};
+use crate::utils::keyfile::{
+    ensure_no_rotation_in_progress, read_key_file, write_key_file, KeyFile, StoredKey,
+};
+use crate::utils::lock::lock_locker;
+use crate::utils::passphrase::read_passphrase;
+use crate::utils::toolbox::get_locker_dir;
+use crate::LockerResult;
+use colored::Colorize;
+
+/// Changes the passphrase protecting the locker key.
+///
+/// The same data key is re-wrapped under a key derived from the new passphrase
+/// with a fresh salt and the current KDF settings. Only `locker.key` is
+/// rewritten, atomically; the `.slock` files are left untouched.
+///
+/// The current passphrase is read from the configured passphrase source.
+/// Legacy lockers with a clear-text key are wrapped for the first time, in
+/// which case no current passphrase is needed. The change is refused while a
+/// key rotation is in progress.
+///
+/// # Arguments
+/// * `new_passphrase` - The passphrase to protect the key with from now on.
+pub fn change_passphrase(new_passphrase: &str) -> LockerResult<()> {
+    let locker_dir = get_locker_dir()?;
+    let _lock = lock_locker(&locker_dir)?;
+    // Une rotation reprise remplacerait la clé par `locker.key.new`, enveloppée sous l'ancienne passphrase
+    ensure_no_rotation_in_progress(&locker_dir)?;
+
+    let (data_key, kdf) = match read_key_file(&locker_dir)? {
+        StoredKey::Wrapped(key_file) => {
+            let old_passphrase = read_passphrase("🔑 Current passphrase: ")?;
+            (
+                key_file.unwrap_key(old_passphrase.expose())?,
+                key_file.params.kdf.clone(),
+            )
+        }
+        StoredKey::Legacy(raw_key) => (raw_key, Kdf::default()),
+    };
+
+    let key_file = KeyFile::wrap(&data_key, new_passphrase, kdf)?;
+    write_key_file(&locker_dir, &key_file)?;
+    remove_key_params(&locker_dir)?;
+
+    println!("{}", "✅ Passphrase changed successfully.".green());
+    Ok(())
+}
@@ -1,50 +1,42 @@
-use crate::utils::metadata::{read_metadata, remove_metadata};
+use crate::commands::open_locker;
+use crate::utils::interaction::confirm;
 use crate::utils::toolbox::get_locker_dir;
-use crate::MetadataFile;
 use crate::SmartLockerError;
-use std::fs;
+use colored::Colorize;
 
-pub fn remove_secret(name: Option<&str>, remove_all: bool) -> Result<(), SmartLockerError> {
-    let locker_dir = get_locker_dir()?;
-
-    if !locker_dir.exists() {
+/// Moves a secret, or every secret with `remove_all`, to the trash.
+///
+/// Removing every secret asks for confirmation, unless `force` is set.
+pub fn remove_secret(
+    name: Option<&str>,
+    remove_all: bool,
+    force: bool,
+) -> Result<(), SmartLockerError> {
+    if !get_locker_dir()?.exists() {
         println!("No secure folder found. Run `init` to create it.");
         return Ok(());
     }
 
-    let mut metadata = read_metadata().unwrap_or_else(|_| MetadataFile {
-        secrets: Default::default(),
-    });
-
     if remove_all {
-        // Supprimer tous les secrets et leurs fichiers
-        for secret_name in metadata.secrets.keys().cloned().collect::<Vec<_>>() {
-            let file_path = locker_dir.join(format!("{}.slock", secret_name));
-            if file_path.exists() {
-                fs::remove_file(&file_path).map_err(|e| {
-                    SmartLockerError::FileSystemError(format!(
-                        "Error when deleting the file: {}",
-                        e
-                    ))
-                })?;
-            }
+        let locker = open_locker()?;
+        let count = locker.list()?.len();
+        let question = format!("⚠️ Move all {} secret(s) to the trash?", count);
+        if !force && !confirm(&question.yellow().to_string())? {
+            return Err(SmartLockerError::UnknownError(
+                "Removal of all secrets cancelled (use --force to skip the confirmation)"
+                    .to_string(),
+            ));
         }
-
-        // Supprimer toutes les métadonnées
-        remove_metadata(None, &mut metadata)?;
-        println!("All secrets and their metadata have been successfully deleted.");
+        // Déplacer tous les secrets et leurs métadonnées dans la corbeille
+        locker.remove_all()?;
+        println!(
+            "All secrets have been moved to the trash. Use `trash purge` to delete them for good."
+        );
     } else if let Some(secret_name) = name {
-        // Vérifier si le fichier existe
-        let file_path = locker_dir.join(format!("{}.slock", secret_name));
-        if file_path.exists() {
-            fs::remove_file(&file_path).map_err(|e| {
-                SmartLockerError::FileSystemError(format!("Error when deleting the file: {}", e))
-            })?;
-            // Supprimer les métadonnées associées
-            remove_metadata(Some(secret_name), &mut metadata)?;
+        if open_locker()?.remove(secret_name)? {
             println!(
-                "Secret '{}' and its metadata have been successfully deleted.",
-                secret_name
+                "Secret '{}' has been moved to the trash. Use `restore -n {}` to bring it back.",
+                secret_name, secret_name
             );
         } else {
             println!("Secret '{}' doesn't exist.", secret_name);
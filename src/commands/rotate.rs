@@ -0,0 +1,282 @@
+use crate::commands::init::generate_key;
+use crate::utils::config::LockerConfig;
+use crate::utils::crypto::{open_secret, seal_secret};
+use crate::utils::kdf::{remove_key_params, Kdf};
+use crate::utils::keyfile::{read_key_file, KeyFile, StoredKey, KEY_FILE, ROTATION_JOURNAL};
+use crate::utils::lock::lock_locker;
+use crate::utils::metadata::{
+    find_unindexed_secret_files, has_legacy_metadata, indexed_secret_files, read_metadata,
+    seal_metadata, METADATA_INDEX_FILE,
+};
+use crate::utils::passphrase::{read_confirmed_passphrase, read_passphrase};
+use crate::utils::secret::SecretBytes;
+use crate::utils::toolbox::{get_locker_dir, sync_parent_dir, write_atomic};
+use crate::LockerResult;
+use crate::MetadataFile;
+use crate::SmartLockerError;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub(crate) const NEW_KEY_FILE: &str = "locker.key.new"; // Nouvelle clé en attente de validation
+const STAGING_EXTENSION: &str = "rotating"; // Suffixe des secrets rechiffrés en attente
+
+/// Phase of a key rotation, recorded in the journal.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+enum RotationState {
+    /// Secrets are being re-encrypted into staging files; the old key is still in use.
+    Staging,
+    /// Every staging file is verified; they are being swapped in with the new key.
+    Committing,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct RotationJournal {
+    state: RotationState,
+    /// Stems of the staged `.slock` files (opaque identifiers, or names for older files).
+    #[serde(default)]
+    secrets: Vec<String>,
+}
+
+/// Generates a new data key and re-encrypts every secret under it.
+///
+/// The rotation is a crash-safe transaction recorded in `rotation.journal`:
+/// 1. the new key is wrapped under the passphrase into `locker.key.new`;
+/// 2. every secret is re-encrypted with a fresh nonce, under the cipher set in
+///    `config.json`, into `<name>.slock.rotating` and checked to decrypt to the same value;
+/// 3. the metadata index is sealed under the new key into `metadata.index.rotating`;
+/// 4. the staging files and the new key are swapped in.
+///
+/// The old key is only replaced once every secret round-trips. If the command
+/// is interrupted, running it again resumes the rotation where it stopped.
+pub fn rotate_key() -> LockerResult<()> {
+    let locker_dir = get_locker_dir()?;
+    let _lock = lock_locker(&locker_dir)?;
+    let journal_path = locker_dir.join(ROTATION_JOURNAL);
+
+    if journal_path.exists() {
+        let journal = read_journal(&journal_path)?;
+        if journal.state == RotationState::Committing {
+            println!("{}", "⚠️ Resuming an interrupted key rotation...".yellow());
+            return commit_rotation(&locker_dir, &journal);
+        }
+        println!(
+            "{}",
+            "⚠️ Restarting an interrupted key rotation...".yellow()
+        );
+    }
+
+    // Déverrouiller l'ancienne clé ; une clé en clair n'a pas de passphrase à vérifier,
+    // celle qui protégera la nouvelle clé est donc saisie deux fois
+    let (old_key, kdf, passphrase) = match read_key_file(&locker_dir)? {
+        StoredKey::Wrapped(key_file) => {
+            let passphrase = read_passphrase("🔑 Passphrase: ")?;
+            (
+                key_file.unwrap_key(passphrase.expose())?,
+                key_file.params.kdf.clone(),
+                passphrase,
+            )
+        }
+        StoredKey::Legacy(raw_key) => (
+            raw_key,
+            Kdf::default(),
+            read_confirmed_passphrase("🔑 Choose a passphrase to protect the new key: ")?,
+        ),
+    };
+
+    // Reprendre la nouvelle clé d'une rotation interrompue, ou en générer une
+    let new_key_path = locker_dir.join(NEW_KEY_FILE);
+    let new_key = if new_key_path.exists() {
+        match serde_json::from_slice::<KeyFile>(&read_file(&new_key_path)?) {
+            Ok(key_file) => Some(key_file.unwrap_key(passphrase.expose())?),
+            Err(_) => None, // Fichier tronqué : on repart d'une nouvelle clé
+        }
+    } else {
+        None
+    };
+    let new_key = match new_key {
+        Some(new_key) => new_key,
+        None => {
+            let new_key = SecretBytes::new(generate_key());
+            let content =
+                serde_json::to_vec_pretty(&KeyFile::wrap(&new_key, passphrase.expose(), kdf)?)
+                    .map_err(|e| {
+                        SmartLockerError::FileSystemError(format!(
+                            "Error serializing the key file: {}",
+                            e
+                        ))
+                    })?;
+            write_journal(
+                &journal_path,
+                &RotationJournal {
+                    state: RotationState::Staging,
+                    secrets: Vec::new(),
+                },
+            )?;
+            write_atomic(&new_key_path, &content)?;
+            new_key
+        }
+    };
+
+    // Rechiffrer chaque secret dans un fichier de staging et vérifier l'aller-retour
+    let cipher = LockerConfig::load(&locker_dir)?.cipher;
+    let legacy_metadata = has_legacy_metadata(&locker_dir);
+    let metadata = read_metadata(&old_key)?;
+    if legacy_metadata {
+        println!(
+            "{}",
+            "🔒 Metadata encrypted and secret file names hidden.".green()
+        );
+    }
+    let mut secrets = Vec::new();
+    for (secret_name, secret_path) in list_secret_files(&locker_dir, &metadata)? {
+        let plaintext = open_secret(&secret_name, &read_file(&secret_path)?, &old_key)?;
+        let staging_path = staging_path(&secret_path);
+
+        let already_staged = staging_path.exists()
+            && read_file(&staging_path)
+                .and_then(|data| open_secret(&secret_name, &data, &new_key))
+                .map(|staged| staged.expose() == plaintext.expose())
+                .unwrap_or(false);
+        if !already_staged {
+            let encrypted_data = seal_secret(&secret_name, plaintext.expose(), &new_key, cipher)?;
+            let round_trip = open_secret(&secret_name, &encrypted_data, &new_key)?;
+            if round_trip.expose() != plaintext.expose() {
+                return Err(SmartLockerError::EncryptionError(format!(
+                    "The secret '{}' does not round-trip under the new key. Rotation aborted.",
+                    secret_name
+                )));
+            }
+            write_atomic(&staging_path, &encrypted_data)?;
+        }
+        secrets.push(file_stem(&secret_path));
+    }
+
+    // Sceller l'index des métadonnées avec la nouvelle clé
+    let index_path = locker_dir.join(METADATA_INDEX_FILE);
+    if index_path.exists() {
+        write_atomic(
+            &staging_path(&index_path),
+            &seal_metadata(&metadata, &new_key, cipher)?,
+        )?;
+    }
+
+    let journal = RotationJournal {
+        state: RotationState::Committing,
+        secrets,
+    };
+    write_journal(&journal_path, &journal)?;
+    commit_rotation(&locker_dir, &journal)
+}
+
+/// Swaps the staging files and the new key in, then removes the journal.
+///
+/// Every step can be replayed, so an interrupted commit is finished by running it again.
+/// The directory is synced before the journal is removed, so the journal never
+/// disappears while a rename is still only in memory.
+fn commit_rotation(locker_dir: &Path, journal: &RotationJournal) -> LockerResult<()> {
+    for file_stem in &journal.secrets {
+        let secret_path = locker_dir.join(format!("{}.slock", file_stem));
+        let staging_path = staging_path(&secret_path);
+        if staging_path.exists() {
+            fs::rename(&staging_path, &secret_path).map_err(|e| {
+                SmartLockerError::FileSystemError(format!(
+                    "Error replacing secret file '{}': {}",
+                    file_stem, e
+                ))
+            })?;
+        }
+    }
+
+    let index_path = locker_dir.join(METADATA_INDEX_FILE);
+    let staged_index_path = staging_path(&index_path);
+    if staged_index_path.exists() {
+        fs::rename(&staged_index_path, &index_path).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error replacing the metadata index: {}", e))
+        })?;
+    }
+
+    let new_key_path = locker_dir.join(NEW_KEY_FILE);
+    if new_key_path.exists() {
+        // Valider le fichier avant de remplacer l'ancienne clé
+        serde_json::from_slice::<KeyFile>(&read_file(&new_key_path)?).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("The new key file is corrupted: {}", e))
+        })?;
+        fs::rename(&new_key_path, locker_dir.join(KEY_FILE)).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error installing the new key: {}", e))
+        })?;
+    }
+
+    // Les paramètres d'une ancienne clé en clair sont désormais dans le fichier de clé
+    remove_key_params(locker_dir)?;
+
+    // Les renommages doivent être sur disque avant que le journal disparaisse
+    let journal_path = locker_dir.join(ROTATION_JOURNAL);
+    sync_parent_dir(&journal_path).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error syncing the locker directory: {}", e))
+    })?;
+    fs::remove_file(&journal_path)
+        .and_then(|_| sync_parent_dir(&journal_path))
+        .map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error removing the rotation journal: {}", e))
+        })?;
+
+    println!(
+        "{}",
+        format!(
+            "✅ Key rotated and {} secret(s) re-encrypted.",
+            journal.secrets.len()
+        )
+        .green()
+    );
+    Ok(())
+}
+
+/// Lists every secret file, previous versions and trash included, with the name its content is bound to.
+///
+/// Indexed files are named after an opaque identifier; files not migrated yet
+/// are still named after the secret.
+fn list_secret_files(
+    locker_dir: &Path,
+    metadata: &MetadataFile,
+) -> LockerResult<Vec<(String, PathBuf)>> {
+    let mut secrets: Vec<(String, PathBuf)> = indexed_secret_files(locker_dir, metadata)
+        .into_iter()
+        .filter(|(_, path)| path.exists())
+        .collect();
+    secrets.extend(find_unindexed_secret_files(locker_dir, metadata)?);
+    Ok(secrets)
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn staging_path(secret_path: &Path) -> PathBuf {
+    let mut staging = secret_path.as_os_str().to_owned();
+    staging.push(format!(".{}", STAGING_EXTENSION));
+    PathBuf::from(staging)
+}
+
+fn read_file(path: &Path) -> LockerResult<Vec<u8>> {
+    fs::read(path)
+        .map_err(|e| SmartLockerError::FileSystemError(format!("Unable to read {:?}: {}", path, e)))
+}
+
+fn read_journal(journal_path: &Path) -> LockerResult<RotationJournal> {
+    serde_json::from_slice(&read_file(journal_path)?).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error parsing the rotation journal: {}", e))
+    })
+}
+
+fn write_journal(journal_path: &Path, journal: &RotationJournal) -> LockerResult<()> {
+    let content = serde_json::to_vec_pretty(journal).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error serializing the rotation journal: {}", e))
+    })?;
+    write_atomic(journal_path, &content)
+}
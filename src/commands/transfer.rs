@@ -0,0 +1,44 @@
+use crate::commands::{open_locker, open_locker_with_passphrase};
+use crate::locker::Locker;
+use crate::utils::passphrase::{passphrase_source, read_passphrase, PassphraseSource};
+use crate::utils::toolbox::{get_locker_dir, home_locker_dir};
+use crate::utils::vault::VaultRegistry;
+use crate::LockerResult;
+use crate::SmartLockerError;
+
+/// Renames a secret, keeping its metadata and versions.
+///
+/// An existing secret with the new name is moved to the trash with `force`,
+/// and the rename is refused otherwise.
+pub fn rename_secret(from: &str, to: &str, force: bool) -> LockerResult<()> {
+    open_locker()?.rename(from, to, force)
+}
+
+/// Copies a secret under another name, with its metadata and versions.
+pub fn copy_secret(from: &str, to: &str, force: bool) -> LockerResult<()> {
+    open_locker()?.copy(from, to, force)
+}
+
+/// Moves a secret to a named vault, with its metadata and versions.
+///
+/// The secret is re-encrypted with the key of the target vault. The passphrase
+/// is read once and unlocks both vaults; if the target vault uses another
+/// passphrase, it is asked for on the terminal, naming the vault.
+pub fn move_secret(name: &str, to_vault: &str, force: bool) -> LockerResult<()> {
+    let home_dir = home_locker_dir()?;
+    let target_dir = VaultRegistry::load(&home_dir)?.path_of(to_vault, &home_dir)?;
+    let passphrase = read_passphrase("🔑 Passphrase: ")?;
+    let source = open_locker_with_passphrase(&get_locker_dir()?, &passphrase)?;
+
+    let target = match open_locker_with_passphrase(&target_dir, &passphrase) {
+        Err(SmartLockerError::DecryptionError(_))
+            if matches!(passphrase_source()?, PassphraseSource::Prompt) =>
+        {
+            let passphrase =
+                read_passphrase(&format!("🔑 Passphrase of the vault '{}': ", to_vault))?;
+            Locker::open_with_passphrase(&target_dir, &passphrase)?
+        }
+        target => target?,
+    };
+    source.move_to(&target, name, force)
+}
@@ -0,0 +1,37 @@
+use crate::commands::open_locker;
+use crate::LockerResult;
+use chrono::DateTime;
+use colored::Colorize;
+
+/// Describes the secrets in the trash, in the order they were removed.
+pub fn list_trash() -> LockerResult<Vec<String>> {
+    Ok(open_locker()?
+        .trash()?
+        .iter()
+        .map(|trashed| {
+            let deleted_at = DateTime::from_timestamp(trashed.deleted_at() as i64, 0)
+                .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| "Invalid timestamp".to_string());
+            format!(
+                "{}  Deleted At: {}  Versions: {}",
+                trashed.secret().name().blue(),
+                deleted_at,
+                trashed.secret().history().len() + 1
+            )
+        })
+        .collect())
+}
+
+/// Brings a secret back from the trash, with its versions.
+pub fn restore_secret(name: &str) -> LockerResult<()> {
+    open_locker()?.restore(name)
+}
+
+/// Deletes for good the secrets removed more than `older_than` seconds ago, or the whole trash.
+///
+/// # Returns
+/// * `Ok(usize)` - The number of secrets purged.
+/// * `Err(SmartLockerError)` - If the index or a file cannot be written.
+pub fn purge_trash(older_than: Option<u64>) -> LockerResult<usize> {
+    open_locker()?.purge(older_than)
+}
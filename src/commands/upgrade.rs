@@ -0,0 +1,52 @@
+use crate::utils::kdf::{derive_key, read_key_params, remove_key_params, Kdf, KeyParams};
+use crate::utils::keyfile::{
+    ensure_no_rotation_in_progress, read_key_file, write_key_file, KeyFile, StoredKey,
+};
+use crate::utils::lock::lock_locker;
+use crate::utils::toolbox::get_locker_dir;
+use crate::LockerResult;
+use crate::SmartLockerError;
+use colored::Colorize;
+
+/// Re-wraps the locker key under a key derived with a new KDF.
+///
+/// The data key itself is unchanged, so no secret needs to be re-encrypted.
+/// Legacy lockers whose raw key was derived from the passphrase are checked
+/// against the passphrase, then wrapped for the first time. The upgrade is
+/// refused while a key rotation is in progress.
+///
+/// # Arguments
+/// * `passphrase` - The passphrase protecting the locker.
+/// * `kdf` - The new key-derivation function (usually Argon2id).
+pub fn upgrade_kdf(passphrase: &str, kdf: Kdf) -> LockerResult<()> {
+    let locker_dir = get_locker_dir()?;
+    let _lock = lock_locker(&locker_dir)?;
+    // Une rotation reprise remplacerait la clé par `locker.key.new`, sous l'ancien KDF
+    ensure_no_rotation_in_progress(&locker_dir)?;
+
+    let data_key = match read_key_file(&locker_dir)? {
+        StoredKey::Wrapped(key_file) => key_file.unwrap_key(passphrase)?,
+        StoredKey::Legacy(raw_key) => {
+            // Vérifier que la passphrase correspond à la clé brute actuelle
+            let params = read_key_params(&locker_dir)?.unwrap_or_else(KeyParams::legacy);
+            if derive_key(passphrase, &params)?.expose() != raw_key.expose() {
+                return Err(SmartLockerError::InitializationError(
+                    "The passphrase does not match the current key (or the locker uses a random key)."
+                        .to_string(),
+                ));
+            }
+            raw_key
+        }
+    };
+
+    let key_file = KeyFile::wrap(&data_key, passphrase, kdf)?;
+    write_key_file(&locker_dir, &key_file)?;
+
+    remove_key_params(&locker_dir)?;
+
+    println!(
+        "{}",
+        format!("✅ Key upgraded to {}.", key_file.params.kdf.name()).green()
+    );
+    Ok(())
+}
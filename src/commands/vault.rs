@@ -0,0 +1,89 @@
+use crate::locker::{Locker, LockerOptions};
+use crate::utils::keyfile::KEY_FILE;
+use crate::utils::lock::{FileLock, REGISTRY_LOCK_FILE};
+use crate::utils::passphrase::read_confirmed_passphrase;
+use crate::utils::toolbox::home_locker_dir;
+use crate::utils::vault::{resolve_vault, ResolvedVault, VaultRegistry, DEFAULT_VAULT_NAME};
+use crate::LockerResult;
+use colored::Colorize;
+use std::path::Path;
+
+/// Registers a new vault and initializes its locker.
+///
+/// A directory that already holds a locker is registered as is. Otherwise a
+/// key is generated, protected by a passphrase read from the configured source.
+pub fn create_vault(name: &str, path: Option<&Path>) -> LockerResult<()> {
+    let home_dir = home_locker_dir()?;
+    let _lock = FileLock::acquire(&home_dir.join(REGISTRY_LOCK_FILE))?;
+    let mut registry = VaultRegistry::load(&home_dir)?;
+    let vault_dir = registry.add(name, path, &home_dir)?;
+
+    if vault_dir.join(KEY_FILE).exists() {
+        println!("🔑 Existing locker registered: {:?}", vault_dir);
+    } else {
+        let passphrase = read_confirmed_passphrase("🔑 Choose a passphrase to protect the key: ")?;
+        Locker::create(&vault_dir, LockerOptions::new(passphrase))?;
+        println!("✅ Locker created: {:?}", vault_dir);
+    }
+
+    // Le registre n'est écrit qu'une fois le locker prêt
+    registry.save(&home_dir)?;
+    println!(
+        "{}",
+        format!("✅ Vault '{}' created successfully!", name).green()
+    );
+    Ok(())
+}
+
+/// Lists the vaults, the default one marked with `*`.
+pub fn list_vaults() -> LockerResult<Vec<String>> {
+    let home_dir = home_locker_dir()?;
+    let registry = VaultRegistry::load(&home_dir)?;
+
+    let vaults = std::iter::once((DEFAULT_VAULT_NAME, &home_dir)).chain(
+        registry
+            .vaults
+            .iter()
+            .map(|(name, path)| (name.as_str(), path)),
+    );
+    Ok(vaults
+        .map(|(name, path)| {
+            let marker = if name == registry.default_name() {
+                "*"
+            } else {
+                " "
+            };
+            format!("{} {}  {}", marker, name.blue(), path.display())
+        })
+        .collect())
+}
+
+/// Unregisters a vault. Its files are left on disk.
+pub fn remove_vault(name: &str) -> LockerResult<()> {
+    let home_dir = home_locker_dir()?;
+    let _lock = FileLock::acquire(&home_dir.join(REGISTRY_LOCK_FILE))?;
+    let mut registry = VaultRegistry::load(&home_dir)?;
+    let path = registry.remove(name)?;
+    registry.save(&home_dir)?;
+    println!(
+        "✅ Vault '{}' removed from the registry. Its files are kept in {:?}.",
+        name, path
+    );
+    Ok(())
+}
+
+/// Makes a vault the one used when no `--vault` is given.
+pub fn set_default_vault(name: &str) -> LockerResult<()> {
+    let home_dir = home_locker_dir()?;
+    let _lock = FileLock::acquire(&home_dir.join(REGISTRY_LOCK_FILE))?;
+    let mut registry = VaultRegistry::load(&home_dir)?;
+    registry.set_default(name)?;
+    registry.save(&home_dir)?;
+    println!("✅ '{}' is now the default vault.", name);
+    Ok(())
+}
+
+/// Reports which vault the commands use from here, and why.
+pub fn current_vault() -> LockerResult<ResolvedVault> {
+    resolve_vault()
+}
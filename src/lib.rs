@@ -7,17 +7,38 @@ pub type LockerResult<T> = Result<T, SmartLockerError>;
 
 pub mod commands;
 pub use crate::commands::{
-    decrypt::decrypt,
-    encrypt::encrypt,
+    backup::{backup_vault, restore_vault},
+    decrypt::{
+        decrypt, decrypt_bytes, decrypt_to_file, decrypt_version_bytes, decrypt_version_to_file,
+    },
+    doctor::doctor,
+    encrypt::{encrypt, encrypt_bytes, encrypt_file},
     export::export,
-    init::{backup_key, init_locker_with_passphrase, restore_key},
+    history::{rollback_secret, secret_history},
+    init::{
+        backup_key, init_locker_with_kdf, init_locker_with_passphrase, restore_key,
+        set_locker_cipher, set_locker_keep_versions,
+    },
     list::list_secrets,
+    passwd::change_passphrase,
     remove::remove_secret,
     renew::renew_secret,
+    rotate::rotate_key,
+    transfer::{copy_secret, move_secret, rename_secret},
+    trash::{list_trash, purge_trash, restore_secret},
+    upgrade::upgrade_kdf,
 };
+pub mod locker;
 pub mod utils;
 
-pub use crate::utils::config::EncryptionConfig;
+pub use crate::locker::{
+    ConflictPolicy, Locker, LockerEvent, LockerOptions, RestoreReport, VaultIssue,
+};
+
+pub use crate::utils::config::{Cipher, EncryptionConfig, LockerConfig};
+pub use crate::utils::format::FormatError;
+pub use crate::utils::kdf::Kdf;
+pub use crate::utils::secret::{SecretBytes, SecretString};
 
 pub use crate::utils::toolbox::{copy_to_clipboard, get_locker_dir};
 
@@ -29,10 +50,54 @@ pub enum SmartLockerError {
     EncryptionError(String),
     #[error("Decryption error: {0}")]
     DecryptionError(String),
+    #[error("Invalid secret file: {0}")]
+    FormatError(#[from] FormatError),
+    #[error("Secret mismatch: {0}")]
+    SecretMismatch(String),
+    #[error("Invalid secret name {0}")]
+    InvalidSecretName(String),
     #[error("Initialization error: {0}")]
     InitializationError(String),
     #[error("Unknown error: {0}")]
     UnknownError(String),
+    #[error("Restore interrupted: {error}")]
+    RestoreInterrupted {
+        /// The secrets imported before the failure, which stay in the locker.
+        report: RestoreReport,
+        #[source]
+        error: Box<SmartLockerError>,
+    },
+}
+
+/// Kind of value held by a secret, so binary values are never printed to a terminal.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    /// UTF-8 text, printable as is.
+    #[default]
+    Text,
+    /// Arbitrary bytes (certificates, keystores, binary tokens).
+    Binary,
+}
+
+impl ValueType {
+    /// Text if the bytes are valid UTF-8, binary otherwise.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if std::str::from_utf8(bytes).is_ok() {
+            ValueType::Text
+        } else {
+            ValueType::Binary
+        }
+    }
+}
+
+impl std::fmt::Display for ValueType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValueType::Text => f.write_str("text"),
+            ValueType::Binary => f.write_str("binary"),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -42,9 +107,110 @@ pub struct SecretMetadata {
     expire_at: u64,
     expired: bool,
     tags: Vec<String>,
+    #[serde(default)]
+    id: String, // Identifiant opaque du fichier `<id>.slock`
+    #[serde(default)]
+    value_type: ValueType, // Les anciens secrets sont du texte
+    #[serde(default = "first_version")]
+    version: u32, // Numéro de la valeur actuelle, à partir de 1
+    #[serde(default)]
+    history: Vec<SecretVersion>, // Valeurs précédentes, de la plus ancienne à la plus récente
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+/// A previous value of a secret, kept in its own `.slock` file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SecretVersion {
+    version: u32,
+    id: String, // Identifiant opaque du fichier `<id>.slock`
+    created_at: u64,
+    #[serde(default)]
+    value_type: ValueType,
+}
+
+impl SecretVersion {
+    /// Returns the number of this version.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns the opaque identifier naming the file of this version.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns when this value was encrypted, as a Unix timestamp.
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    /// Returns whether this value holds text or binary data.
+    pub fn value_type(&self) -> ValueType {
+        self.value_type
+    }
 }
 
 impl SecretMetadata {
+    /// Returns the name of the secret.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns when the secret was encrypted, as a Unix timestamp.
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    /// Returns when the secret expires, as a Unix timestamp.
+    pub fn expire_at(&self) -> u64 {
+        self.expire_at
+    }
+
+    /// Returns `true` if the secret was marked as expired.
+    pub fn is_expired(&self) -> bool {
+        self.expired
+    }
+
+    /// Returns the tags of the secret.
+    pub fn tags(&self) -> &[String] {
+        &self.tags
+    }
+
+    /// Returns the opaque identifier naming the secret file on disk.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns whether the secret holds text or binary data.
+    pub fn value_type(&self) -> ValueType {
+        self.value_type
+    }
+
+    /// Returns the number of the current value.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Returns the previous values kept, oldest first.
+    pub fn history(&self) -> &[SecretVersion] {
+        &self.history
+    }
+
+    /// Returns every value of the secret, oldest first, the current one last.
+    pub fn versions(&self) -> Vec<SecretVersion> {
+        let mut versions = self.history.clone();
+        versions.push(SecretVersion {
+            version: self.version,
+            id: self.id.clone(),
+            created_at: self.created_at,
+            value_type: self.value_type,
+        });
+        versions
+    }
+
     pub fn field_count(instance: Option<&Self>) -> usize {
         // Si une instance est fournie, sérialiser cette instance
         let json_value = if let Some(instance) = instance {
@@ -57,6 +223,10 @@ impl SecretMetadata {
                 expire_at: 0,
                 expired: false,
                 tags: Vec::new(),
+                id: String::new(),
+                value_type: ValueType::Text,
+                version: first_version(),
+                history: Vec::new(),
             })
             .expect("Failed to serialize default instance")
         };
@@ -70,7 +240,28 @@ impl SecretMetadata {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct MetadataFile {
     pub secrets: HashMap<String, SecretMetadata>, // Clé : nom du secret
+    #[serde(default)]
+    pub trash: Vec<TrashedSecret>, // Secrets supprimés, du plus ancien au plus récent
+}
+
+/// A removed secret, kept with its versions until the trash is purged.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct TrashedSecret {
+    secret: SecretMetadata,
+    deleted_at: u64,
+}
+
+impl TrashedSecret {
+    /// Returns the metadata of the secret as it was when removed.
+    pub fn secret(&self) -> &SecretMetadata {
+        &self.secret
+    }
+
+    /// Returns when the secret was removed, as a Unix timestamp.
+    pub fn deleted_at(&self) -> u64 {
+        self.deleted_at
+    }
 }
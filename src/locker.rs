@@ -0,0 +1,1213 @@
+use crate::commands::export::ExportFormat;
+use crate::commands::init::write_wrapped_key;
+use crate::utils::config::{Cipher, LockerConfig, STREAM_CHUNK_SIZE, TAG_SIZE};
+use crate::utils::crypto::{find_bound_name, open_secret, open_secret_stream, seal_secret_stream};
+use crate::utils::format::MAX_HEADER_SIZE;
+use crate::utils::interaction::Interaction;
+use crate::utils::kdf::Kdf;
+use crate::utils::keyfile::{load_master_key, unlock_master_key, KEY_FILE};
+use crate::utils::lock::lock_locker;
+use crate::utils::metadata::{
+    find_unindexed_secret_files, generate_secret_id, has_legacy_metadata, is_metadata_current,
+    is_secret_expired, read_metadata_in, secret_file_path, secret_version_path, write_metadata_in,
+};
+use crate::utils::name::validate_secret_name;
+use crate::utils::passphrase::PassphraseSource;
+use crate::utils::secret::{SecretBytes, SecretString};
+use crate::utils::toolbox::{
+    copy_atomic, create_private_dir, create_private_file, sync_parent_dir,
+};
+use crate::{
+    LockerResult, MetadataFile, SecretMetadata, SecretVersion, SmartLockerError, TrashedSecret,
+    ValueType,
+};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+mod backup;
+mod doctor;
+pub use backup::{ConflictPolicy, RestoreReport};
+pub use doctor::VaultIssue;
+
+const DEFAULT_EXPIRATION_DAYS: u64 = 15; // Expiration par défaut des secrets
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Something that happened in a locker as a side effect of an operation.
+///
+/// The [`Locker`] never prints: events are handed to the observer set with
+/// [`Locker::with_observer`], and the CLI decides how to show them.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LockerEvent {
+    /// A clear-text `metadata.json` left by an older version was sealed into the index.
+    LegacyMetadataSealed,
+    /// A secret was found past its expiration date and marked as expired.
+    SecretExpired(String),
+    /// Metadata was created for a secret missing from the index or with outdated metadata.
+    MetadataMigrated(String),
+}
+
+/// Options used to create a locker with [`Locker::create`].
+#[derive(Clone, Debug)]
+pub struct LockerOptions {
+    /// Passphrase protecting the locker key.
+    pub passphrase: SecretString,
+    /// Key derivation function applied to the passphrase.
+    pub kdf: Kdf,
+    /// Cipher used for new secrets.
+    pub cipher: Cipher,
+}
+
+impl LockerOptions {
+    /// Options with the default KDF and cipher.
+    pub fn new(passphrase: SecretString) -> Self {
+        Self {
+            passphrase,
+            kdf: Kdf::default(),
+            cipher: Cipher::default(),
+        }
+    }
+
+    /// Sets the key derivation function.
+    pub fn kdf(mut self, kdf: Kdf) -> Self {
+        self.kdf = kdf;
+        self
+    }
+
+    /// Sets the cipher used for new secrets.
+    pub fn cipher(mut self, cipher: Cipher) -> Self {
+        self.cipher = cipher;
+        self
+    }
+}
+
+type Observer = Arc<dyn Fn(&LockerEvent) + Send + Sync>;
+
+/// An unlocked locker at an explicit path.
+///
+/// This is the library entry point: it works on the directory it is given and
+/// never looks up the vault of the current user (`SMART_LOCKER_VAULT`, the
+/// vault registry, a local `.smart-locker` directory, `~/.locker` or the
+/// `SMART_LOCKER_TEST_DIR*` variables of the tests). It never prints and never
+/// asks questions. Operations return data, and side
+/// effects worth reporting are sent to the observer as [`LockerEvent`]s.
+///
+/// # Example
+/// ```no_run
+/// use smart_locker::{Locker, LockerOptions, SecretString};
+///
+/// let options = LockerOptions::new(SecretString::from("correct horse battery staple"));
+/// let locker = Locker::create("/tmp/my_locker", options).expect("Failed to create the locker");
+/// locker
+///     .encrypt(&SecretString::from("s3cr3t"), "db_password", Vec::new(), Some(30))
+///     .expect("Failed to encrypt");
+/// let value = locker.decrypt("db_password").expect("Failed to decrypt");
+/// assert_eq!(value.expose(), "s3cr3t");
+/// ```
+pub struct Locker {
+    dir: PathBuf,
+    key_data: SecretBytes,
+    observer: Option<Observer>,
+}
+
+impl fmt::Debug for Locker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Locker")
+            .field("dir", &self.dir)
+            .field("key_data", &self.key_data)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A secret found in the index.
+struct LocatedSecret {
+    metadata: MetadataFile,
+    path: PathBuf,
+    value_type: ValueType,
+}
+
+impl Locker {
+    /// Creates a new locker in `path`: a key wrapped by the passphrase, the
+    /// configuration and an empty index.
+    ///
+    /// # Returns
+    /// * `Ok(Locker)` - The new locker, unlocked.
+    /// * `Err(SmartLockerError)` - `InitializationError` if a locker already exists in `path`.
+    pub fn create(path: impl AsRef<Path>, options: LockerOptions) -> LockerResult<Self> {
+        let dir = path.as_ref().to_path_buf();
+        create_private_dir(&dir).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error creating folder {:?}: {}", dir, e))
+        })?;
+
+        let _lock = lock_locker(&dir)?;
+        if dir.join(KEY_FILE).exists() {
+            return Err(SmartLockerError::InitializationError(format!(
+                "A locker already exists in {:?}",
+                dir
+            )));
+        }
+        LockerConfig {
+            cipher: options.cipher,
+            ..LockerConfig::default()
+        }
+        .save(&dir)?;
+        let key_data = write_wrapped_key(&dir, options.passphrase.expose(), options.kdf)?;
+        let locker = Self::from_key(dir, key_data);
+        locker.write_index(&MetadataFile::default())?;
+        Ok(locker)
+    }
+
+    /// Opens the locker in `path`, reading the passphrase from `source`.
+    ///
+    /// `interaction` decides whether the passphrase may be asked on the
+    /// terminal. Neither is taken from the defaults of the process, so
+    /// several embedders can open lockers side by side. A
+    /// [`PassphraseSource::Fd`] can only be read once: use a
+    /// [`PassphraseSource::Value`] to open several lockers with it.
+    pub fn open(
+        path: impl AsRef<Path>,
+        source: &PassphraseSource,
+        interaction: Interaction,
+    ) -> LockerResult<Self> {
+        let dir = existing_locker_dir(path.as_ref())?;
+        let key_data = load_master_key(&dir, source, interaction)?;
+        Ok(Self::from_key(dir, key_data))
+    }
+
+    /// Opens the locker in `path` with the given passphrase.
+    pub fn open_with_passphrase(
+        path: impl AsRef<Path>,
+        passphrase: &SecretString,
+    ) -> LockerResult<Self> {
+        let dir = existing_locker_dir(path.as_ref())?;
+        let key_data = unlock_master_key(&dir, passphrase.expose())?;
+        Ok(Self::from_key(dir, key_data))
+    }
+
+    /// Wraps a key that is already unlocked.
+    pub(crate) fn from_key(dir: PathBuf, key_data: SecretBytes) -> Self {
+        Self {
+            dir,
+            key_data,
+            observer: None,
+        }
+    }
+
+    /// Sets the function receiving the events of this locker.
+    pub fn with_observer(
+        mut self,
+        observer: impl Fn(&LockerEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Returns the directory of the locker.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Encrypts a text secret and stores it, replacing any secret with the same name.
+    ///
+    /// Without `expiration_days`, the secret expires after 15 days.
+    pub fn encrypt(
+        &self,
+        secret: &SecretString,
+        name: &str,
+        tags: Vec<String>,
+        expiration_days: Option<u64>,
+    ) -> LockerResult<()> {
+        self.store_secret(
+            &mut secret.expose().as_bytes(),
+            Some(ValueType::Text),
+            name,
+            tags,
+            expiration_days,
+        )
+    }
+
+    /// Encrypts arbitrary bytes and stores them.
+    ///
+    /// The value is recorded as text if it is valid UTF-8 and as binary otherwise.
+    pub fn encrypt_bytes(
+        &self,
+        secret: &SecretBytes,
+        name: &str,
+        tags: Vec<String>,
+        expiration_days: Option<u64>,
+    ) -> LockerResult<()> {
+        self.store_secret(&mut secret.expose(), None, name, tags, expiration_days)
+    }
+
+    /// Encrypts the content of a file as a stream, in constant memory, and stores it.
+    pub fn encrypt_file(
+        &self,
+        path: &Path,
+        name: &str,
+        tags: Vec<String>,
+        expiration_days: Option<u64>,
+    ) -> LockerResult<()> {
+        let mut file = fs::File::open(path).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error reading {:?}: {}", path, e))
+        })?;
+        self.store_secret(&mut file, None, name, tags, expiration_days)
+    }
+
+    /// Decrypts a text secret. Binary secrets are refused: use [`Locker::decrypt_bytes`].
+    pub fn decrypt(&self, name: &str) -> LockerResult<SecretString> {
+        let (decrypted_data, value_type) = self.decrypt_bytes(name)?;
+        if value_type == ValueType::Binary {
+            return Err(SmartLockerError::DecryptionError(format!(
+                "The secret '{}' holds binary data. Use --output or --raw to retrieve it.",
+                name
+            )));
+        }
+        SecretString::from_utf8(decrypted_data).map_err(|_| {
+            SmartLockerError::DecryptionError(format!("The secret '{}' is not valid UTF-8", name))
+        })
+    }
+
+    /// Decrypts a secret as raw bytes, with the type recorded when it was encrypted.
+    pub fn decrypt_bytes(&self, name: &str) -> LockerResult<(SecretBytes, ValueType)> {
+        self.read_secret(name, None)
+    }
+
+    /// Decrypts a version of a secret as raw bytes; see [`Locker::history`].
+    pub fn decrypt_version_bytes(
+        &self,
+        name: &str,
+        version: u32,
+    ) -> LockerResult<(SecretBytes, ValueType)> {
+        self.read_secret(name, Some(version))
+    }
+
+    fn read_secret(
+        &self,
+        name: &str,
+        version: Option<u32>,
+    ) -> LockerResult<(SecretBytes, ValueType)> {
+        let secret = self.locate_secret(name, version)?;
+
+        // Lire le fichier chiffré
+        let encrypted_data = fs::read(&secret.path).map_err(|_| {
+            SmartLockerError::FileSystemError("Unable to read the encrypted file".to_string())
+        })?;
+
+        // Déchiffrer et décompresser les données
+        let decrypted_data = open_secret(name, &encrypted_data, &self.key_data)
+            .map_err(|err| self.explain_failure(err, name, &encrypted_data, &secret))?;
+        Ok((decrypted_data, secret.value_type))
+    }
+
+    /// Decrypts a secret into a file readable only by its owner.
+    ///
+    /// The output is written to a temporary file and renamed once every chunk
+    /// has been authenticated, so a tampered secret leaves nothing behind.
+    ///
+    /// # Returns
+    /// * `Ok(u64)` - The number of bytes written.
+    /// * `Err(SmartLockerError)` - If the secret is missing, expired or does not decrypt.
+    pub fn decrypt_to_file(&self, name: &str, output: &Path) -> LockerResult<u64> {
+        self.write_secret(name, None, output)
+    }
+
+    /// Decrypts a version of a secret into a file; see [`Locker::decrypt_to_file`].
+    pub fn decrypt_version_to_file(
+        &self,
+        name: &str,
+        version: u32,
+        output: &Path,
+    ) -> LockerResult<u64> {
+        self.write_secret(name, Some(version), output)
+    }
+
+    fn write_secret(&self, name: &str, version: Option<u32>, output: &Path) -> LockerResult<u64> {
+        let secret = self.locate_secret(name, version)?;
+
+        let mut encrypted_file = fs::File::open(&secret.path).map_err(|_| {
+            SmartLockerError::FileSystemError("Unable to read the encrypted file".to_string())
+        })?;
+        let file_name = output
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| {
+                SmartLockerError::FileSystemError(format!("Invalid file path {:?}", output))
+            })?;
+        let tmp_path = output.with_file_name(format!("{}.tmp", file_name));
+
+        let written = create_private_file(&tmp_path)
+            .map_err(|e| {
+                SmartLockerError::FileSystemError(format!("Error writing {:?}: {}", tmp_path, e))
+            })
+            .and_then(|mut file| {
+                let written =
+                    open_secret_stream(name, &mut encrypted_file, &mut file, &self.key_data)?;
+                file.sync_all()
+                    .and_then(|_| fs::rename(&tmp_path, output))
+                    .map_err(|e| {
+                        SmartLockerError::FileSystemError(format!(
+                            "Error writing {:?}: {}",
+                            output, e
+                        ))
+                    })?;
+                Ok(written)
+            });
+
+        written.map_err(|err| {
+            let _ = fs::remove_file(&tmp_path);
+            // Le début du fichier suffit pour reconnaître un secret déplacé
+            let mut start = Vec::new();
+            let _ = fs::File::open(&secret.path).and_then(|file| {
+                file.take((MAX_HEADER_SIZE + STREAM_CHUNK_SIZE + TAG_SIZE + 1) as u64)
+                    .read_to_end(&mut start)
+            });
+            self.explain_failure(err, name, &start, &secret)
+        })
+    }
+
+    /// Returns the metadata of every secret, sorted by name.
+    pub fn list(&self) -> LockerResult<Vec<SecretMetadata>> {
+        self.list_prefix("")
+    }
+
+    /// Returns the metadata of the secrets whose name starts with `prefix`, sorted by name.
+    ///
+    /// A namespace such as `prod/` lists every secret stored under it.
+    pub fn list_prefix(&self, prefix: &str) -> LockerResult<Vec<SecretMetadata>> {
+        let mut secrets: Vec<SecretMetadata> = self
+            .read_index()?
+            .secrets
+            .into_values()
+            .filter(|secret| secret.name.starts_with(prefix))
+            .collect();
+        secrets.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(secrets)
+    }
+
+    /// Moves a secret and its versions to the trash.
+    ///
+    /// The files stay encrypted on disk until the trash is purged; see [`Locker::restore`].
+    ///
+    /// # Returns
+    /// * `Ok(bool)` - `false` if there was no such secret.
+    /// * `Err(SmartLockerError)` - If the index cannot be written.
+    pub fn remove(&self, name: &str) -> LockerResult<bool> {
+        validate_secret_name(name)?;
+        let deleted_at = now();
+        self.update_index(|metadata| {
+            Ok(match metadata.secrets.remove(name) {
+                Some(secret) => {
+                    metadata.trash.push(TrashedSecret { secret, deleted_at });
+                    true
+                }
+                None => false,
+            })
+        })
+    }
+
+    /// Moves every secret to the trash, returning how many were removed.
+    pub fn remove_all(&self) -> LockerResult<usize> {
+        let deleted_at = now();
+        self.update_index(|metadata| {
+            let mut removed: Vec<SecretMetadata> = std::mem::take(&mut metadata.secrets)
+                .into_values()
+                .collect();
+            removed.sort_by(|a, b| a.name.cmp(&b.name));
+            let count = removed.len();
+            metadata.trash.extend(
+                removed
+                    .into_iter()
+                    .map(|secret| TrashedSecret { secret, deleted_at }),
+            );
+            Ok(count)
+        })
+    }
+
+    /// Returns the secrets in the trash, in the order they were removed.
+    pub fn trash(&self) -> LockerResult<Vec<TrashedSecret>> {
+        Ok(self.read_index()?.trash)
+    }
+
+    /// Brings the most recently removed secret with this name back from the trash.
+    ///
+    /// # Returns
+    /// * `Ok(())` - If the secret is restored with its versions.
+    /// * `Err(SmartLockerError)` - If it is not in the trash, or a secret with the same name exists.
+    pub fn restore(&self, name: &str) -> LockerResult<()> {
+        validate_secret_name(name)?;
+        self.update_index(|metadata| {
+            if metadata.secrets.contains_key(name) {
+                return Err(SmartLockerError::InitializationError(format!(
+                    "A secret named '{}' already exists. Remove it before restoring the trashed one.",
+                    name
+                )));
+            }
+            let position = metadata
+                .trash
+                .iter()
+                .rposition(|trashed| trashed.secret.name == name)
+                .ok_or_else(|| {
+                    SmartLockerError::DecryptionError(format!(
+                        "Secret '{}' is not in the trash.",
+                        name
+                    ))
+                })?;
+            let trashed = metadata.trash.remove(position);
+            metadata.secrets.insert(name.to_string(), trashed.secret);
+            Ok(())
+        })
+    }
+
+    /// Deletes for good the secrets removed more than `older_than` seconds ago,
+    /// or the whole trash without a limit.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - The number of secrets purged.
+    /// * `Err(SmartLockerError)` - If the index or a file cannot be written.
+    pub fn purge(&self, older_than: Option<u64>) -> LockerResult<usize> {
+        let now = now();
+        let purged = self.update_index(|metadata| {
+            let (purged, kept) = std::mem::take(&mut metadata.trash).into_iter().partition(
+                |trashed: &TrashedSecret| {
+                    older_than.is_none_or(|age| trashed.deleted_at.saturating_add(age) <= now)
+                },
+            );
+            metadata.trash = kept;
+            Ok(purged)
+        })?;
+        // Les fichiers ne sont supprimés qu'une fois l'index écrit
+        for trashed in &purged {
+            remove_secret_files(&self.dir, &trashed.secret)?;
+        }
+        Ok(purged.len())
+    }
+
+    /// Pushes the expiration of a secret to `additional_days` from now.
+    ///
+    /// # Returns
+    /// * `Ok(u64)` - The new expiration, as a Unix timestamp.
+    /// * `Err(SmartLockerError)` - If the secret is missing, needs migration or has already expired.
+    pub fn renew(&self, name: &str, additional_days: u64) -> LockerResult<u64> {
+        validate_secret_name(name)?;
+        let renewed = self.update_index(|metadata| {
+            let secret_metadata = metadata
+                .secrets
+                .get_mut(name)
+                .ok_or_else(|| secret_not_found(name))?;
+
+            // Vérifier si le secret est expiré
+            if is_secret_expired(secret_metadata) {
+                secret_metadata.expired = true;
+                return Ok(None);
+            }
+            secret_metadata.expire_at = now() + additional_days * SECONDS_PER_DAY;
+            secret_metadata.expired = false;
+            Ok(Some(secret_metadata.expire_at))
+        })?;
+
+        renewed.ok_or_else(|| self.expired(name))
+    }
+
+    /// Returns every version kept for a secret, oldest first, the current one last.
+    pub fn history(&self, name: &str) -> LockerResult<Vec<SecretVersion>> {
+        validate_secret_name(name)?;
+        let metadata = self.read_index()?;
+        let secret_metadata = metadata
+            .secrets
+            .get(name)
+            .ok_or_else(|| secret_not_found(name))?;
+        Ok(secret_metadata.versions())
+    }
+
+    /// Makes a previous version the current value of a secret again.
+    ///
+    /// The value is copied into a new version, so the history keeps the value
+    /// being replaced and a rollback can itself be undone.
+    ///
+    /// # Returns
+    /// * `Ok(u32)` - The number of the new current version.
+    /// * `Err(SmartLockerError)` - If the secret or the version does not exist.
+    pub fn rollback(&self, name: &str, to_version: u32) -> LockerResult<u32> {
+        validate_secret_name(name)?;
+        let keep_versions = LockerConfig::load(&self.dir)?.keep_versions;
+
+        let _lock = lock_locker(&self.dir)?;
+        let mut metadata = self.read_index_locked()?;
+        let secret_metadata = metadata
+            .secrets
+            .get_mut(name)
+            .ok_or_else(|| secret_not_found(name))?;
+        if secret_metadata.version == to_version {
+            return Ok(to_version);
+        }
+        let target = secret_metadata
+            .history
+            .iter()
+            .find(|version| version.version == to_version)
+            .cloned()
+            .ok_or_else(|| version_not_found(name, to_version))?;
+
+        // Le chiffré reste lié au nom du secret : une copie suffit
+        let id = generate_secret_id();
+        let output_path = self.dir.join(format!("{}.slock", id));
+        copy_atomic(&secret_version_path(&self.dir, &target), &output_path)?;
+
+        let mut history = secret_metadata.versions();
+        let pruned = prune_history(&mut history, keep_versions);
+        secret_metadata.history = history;
+        secret_metadata.version += 1;
+        secret_metadata.id = id;
+        secret_metadata.created_at = now();
+        secret_metadata.value_type = target.value_type;
+        let new_version = secret_metadata.version;
+
+        if let Err(err) = self.write_index(&metadata) {
+            let _ = fs::remove_file(&output_path);
+            return Err(err);
+        }
+        for version in &pruned {
+            remove_secret_file(&secret_version_path(&self.dir, version))?;
+        }
+        Ok(new_version)
+    }
+
+    /// Renames a secret, keeping its metadata and versions.
+    ///
+    /// Every version is re-encrypted, since the name of a secret is bound to its
+    /// ciphertext. An existing secret named `to` is moved to the trash if `force`
+    /// is set, and the rename is refused otherwise.
+    ///
+    /// `from` only has to be in the index, so a secret created by an older
+    /// version under a name outside the grammar can be renamed to a valid one.
+    pub fn rename(&self, from: &str, to: &str, force: bool) -> LockerResult<()> {
+        self.transfer(self, from, to, force, true)
+    }
+
+    /// Copies a secret under another name, with its metadata and versions.
+    ///
+    /// See [`Locker::rename`] for how an existing target is handled.
+    pub fn copy(&self, from: &str, to: &str, force: bool) -> LockerResult<()> {
+        self.transfer(self, from, to, force, false)
+    }
+
+    /// Moves a secret to another locker, with its metadata and versions.
+    ///
+    /// Every version is re-encrypted with the key and cipher of `target`. See
+    /// [`Locker::rename`] for how an existing secret with the same name is handled.
+    pub fn move_to(&self, target: &Locker, name: &str, force: bool) -> LockerResult<()> {
+        self.transfer(target, name, name, force, true)
+    }
+
+    /// Exports the names of the secrets to `output`, in the given format.
+    ///
+    /// Only `env` is supported: each line decrypts one secret with the CLI.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - The number of secrets exported; nothing is written if there are none.
+    /// * `Err(SmartLockerError)` - If the format is unknown or the file cannot be written.
+    pub fn export(&self, format: &str, output: &Path) -> LockerResult<usize> {
+        if format != "env" {
+            return Err(SmartLockerError::UnknownError(format!(
+                "Unsupported export format: {}",
+                format
+            )));
+        }
+        let names: Vec<String> = self.list()?.into_iter().map(|secret| secret.name).collect();
+        if !names.is_empty() {
+            ExportFormat::export_env_with_placeholders(&names, &output.to_path_buf())?;
+        }
+        Ok(names.len())
+    }
+
+    /// Returns `true` if the metadata of a secret is missing or outdated.
+    pub fn needs_migration(&self, name: &str) -> LockerResult<bool> {
+        validate_secret_name(name)?;
+        Ok(!is_metadata_current(name, &self.read_index()?))
+    }
+
+    /// Lists the secrets written by older versions that are not in the index yet.
+    pub fn unindexed_secrets(&self) -> LockerResult<Vec<String>> {
+        let metadata = self.read_index()?;
+        Ok(find_unindexed_secret_files(&self.dir, &metadata)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect())
+    }
+
+    /// Creates the missing metadata of one secret, or of every unindexed secret.
+    ///
+    /// Files `<name>.slock` written by older versions are renamed with an opaque
+    /// identifier once the index is written, so the name of the secret is not revealed.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<String>)` - The names of the migrated secrets.
+    /// * `Err(SmartLockerError)` - If the secret file cannot be found or renamed.
+    pub fn migrate(&self, name: Option<&str>) -> LockerResult<Vec<String>> {
+        if let Some(name) = name {
+            validate_secret_name(name)?;
+        }
+        let _lock = lock_locker(&self.dir)?;
+        let mut metadata = self.read_index_locked()?;
+
+        let now = now();
+        let default_metadata = |name: &str, id: String| SecretMetadata {
+            name: name.to_string(),
+            created_at: now,
+            expire_at: now + DEFAULT_EXPIRATION_DAYS * SECONDS_PER_DAY,
+            expired: false,
+            tags: Vec::new(),
+            id,
+            value_type: ValueType::default(),
+            version: 1,
+            history: Vec::new(),
+        };
+
+        let mut migrated = Vec::new();
+        let mut files_to_rename = Vec::new();
+        if let Some(name) = name {
+            // Migrer une clé spécifique
+            let legacy_path = self.dir.join(format!("{}.slock", name));
+            let indexed = metadata
+                .secrets
+                .get(name)
+                .filter(|secret| secret_file_path(&self.dir, secret).exists())
+                .cloned();
+
+            if let Some(indexed) = indexed {
+                // Réinitialiser des métadonnées obsolètes en gardant le fichier et l'historique
+                metadata.secrets.insert(
+                    name.to_string(),
+                    SecretMetadata {
+                        version: indexed.version,
+                        history: indexed.history,
+                        ..default_metadata(name, indexed.id)
+                    },
+                );
+            } else if legacy_path.exists() {
+                let id = generate_secret_id();
+                files_to_rename.push((legacy_path, id.clone()));
+                metadata
+                    .secrets
+                    .insert(name.to_string(), default_metadata(name, id));
+            } else {
+                return Err(SmartLockerError::FileSystemError(format!(
+                    "Secret file '{}' not found.",
+                    legacy_path.display()
+                )));
+            }
+            migrated.push(name.to_string());
+        } else {
+            // Migrer toutes les clés absentes de l'index
+            for (secret_name, path) in find_unindexed_secret_files(&self.dir, &metadata)? {
+                let id = generate_secret_id();
+                files_to_rename.push((path, id.clone()));
+                metadata
+                    .secrets
+                    .insert(secret_name.clone(), default_metadata(&secret_name, id));
+                migrated.push(secret_name);
+            }
+        }
+
+        // L'index est écrit avant de renommer les fichiers : une migration interrompue
+        // laisse des fichiers `<nom>.slock` qui seront migrés à nouveau
+        self.write_index(&metadata)?;
+        for (path, id) in files_to_rename {
+            fs::rename(&path, self.dir.join(format!("{}.slock", id))).map_err(|e| {
+                SmartLockerError::FileSystemError(format!(
+                    "Error renaming '{}': {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        }
+
+        for name in &migrated {
+            self.emit(LockerEvent::MetadataMigrated(name.clone()));
+        }
+        Ok(migrated)
+    }
+
+    /// Encrypts a secret read from `reader`.
+    ///
+    /// Without an explicit `value_type`, the value is checked for UTF-8 as it is encrypted.
+    fn store_secret<R: Read>(
+        &self,
+        reader: &mut R,
+        value_type: Option<ValueType>,
+        name: &str,
+        tags: Vec<String>,
+        expiration_days: Option<u64>,
+    ) -> LockerResult<()> {
+        validate_secret_name(name)?;
+
+        // Algorithme de chiffrement et rétention de l'historique du locker
+        let config = LockerConfig::load(&self.dir)?;
+        let cipher = config.cipher;
+
+        // Chiffrer dans un nouveau fichier au nom opaque, sans verrou : l'ancienne valeur reste lisible
+        let mut reader = Utf8Detector::new(reader);
+        let id = self.seal_new_file(name, &mut reader, cipher)?;
+        let output_path = self.dir.join(format!("{}.slock", id));
+        let value_type = value_type.unwrap_or_else(|| reader.value_type());
+
+        // Ajouter ou remplacer les métadonnées sous verrou, pour ne perdre aucune écriture concurrente
+        let now = now();
+        let expiration = now + expiration_days.unwrap_or(DEFAULT_EXPIRATION_DAYS) * SECONDS_PER_DAY;
+        let pruned = self.update_index(|metadata| {
+            // L'ancienne valeur rejoint l'historique
+            let (version, mut history) = match metadata.secrets.remove(name) {
+                Some(previous) if previous.id.is_empty() => {
+                    (previous.version + 1, previous.history)
+                }
+                Some(previous) => (previous.version + 1, previous.versions()),
+                None => (1, Vec::new()),
+            };
+            let pruned = prune_history(&mut history, config.keep_versions);
+            metadata.secrets.insert(
+                name.to_string(),
+                SecretMetadata {
+                    name: name.to_string(),
+                    created_at: now,
+                    expire_at: expiration,
+                    expired: false,
+                    tags,
+                    id,
+                    value_type,
+                    version,
+                    history,
+                },
+            );
+            Ok(pruned)
+        });
+
+        // Les versions trop anciennes ne sont supprimées qu'une fois l'index à jour
+        match pruned {
+            Ok(pruned) => {
+                for version in &pruned {
+                    remove_secret_file(&secret_version_path(&self.dir, version))?;
+                }
+                Ok(())
+            }
+            Err(err) => {
+                let _ = fs::remove_file(&output_path);
+                Err(err)
+            }
+        }
+    }
+
+    /// Re-encrypts a secret and its versions as `to` in `target`, then indexes it there.
+    ///
+    /// New files are written first, so an interruption leaves the source untouched.
+    fn transfer(
+        &self,
+        target: &Locker,
+        from: &str,
+        to: &str,
+        force: bool,
+        remove_source: bool,
+    ) -> LockerResult<()> {
+        // `from` est cherché dans l'index : un ancien nom hors grammaire reste utilisable
+        validate_secret_name(to)?;
+        let same_locker = self.is_same_locker(target);
+        if same_locker && from == to {
+            return Err(SmartLockerError::InitializationError(format!(
+                "The secret '{}' cannot replace itself",
+                from
+            )));
+        }
+
+        let source = self
+            .read_index()?
+            .secrets
+            .get(from)
+            .cloned()
+            .ok_or_else(|| secret_not_found(from))?;
+        if source.id.is_empty() {
+            return Err(SmartLockerError::DecryptionError(format!(
+                "Metadata for secret '{}' is missing or outdated. It must be migrated.",
+                from
+            )));
+        }
+        // Refuser avant de rechiffrer quoi que ce soit ; vérifié à nouveau sous verrou
+        if !force && target.read_index()?.secrets.contains_key(to) {
+            return Err(target_exists(to));
+        }
+
+        let mut written = Vec::new();
+        let placed = self
+            .reseal_secret(target, &source, to, &mut written)
+            .and_then(|copy| {
+                let deleted_at = now();
+                let place = |metadata: &mut MetadataFile| -> LockerResult<()> {
+                    if let Some(existing) = metadata.secrets.remove(to) {
+                        if !force {
+                            metadata.secrets.insert(to.to_string(), existing);
+                            return Err(target_exists(to));
+                        }
+                        metadata.trash.push(TrashedSecret {
+                            secret: existing,
+                            deleted_at,
+                        });
+                    }
+                    metadata.secrets.insert(to.to_string(), copy);
+                    Ok(())
+                };
+                if same_locker {
+                    // Un seul index : le renommage est atomique
+                    self.update_index(|metadata| {
+                        if remove_source {
+                            take_unchanged(metadata, &source)?;
+                        }
+                        place(metadata)
+                    })
+                } else {
+                    target.update_index(place)
+                }
+            });
+        if let Err(err) = placed {
+            for path in written {
+                let _ = fs::remove_file(path);
+            }
+            return Err(err);
+        }
+
+        if remove_source {
+            if !same_locker {
+                self.update_index(|metadata| take_unchanged(metadata, &source))
+                    .map_err(|err| {
+                        SmartLockerError::InitializationError(format!(
+                            "The secret '{}' was copied to {:?} but kept here: {}",
+                            from, target.dir, err
+                        ))
+                    })?;
+            }
+            remove_secret_files(&self.dir, &source)?;
+        }
+        Ok(())
+    }
+
+    /// Re-encrypts every version of `source` as `to`, with the key and cipher of `target`.
+    ///
+    /// Returns the metadata of the copy; the paths of the new files are pushed to `written`.
+    fn reseal_secret(
+        &self,
+        target: &Locker,
+        source: &SecretMetadata,
+        to: &str,
+        written: &mut Vec<PathBuf>,
+    ) -> LockerResult<SecretMetadata> {
+        let cipher = LockerConfig::load(&target.dir)?.cipher;
+        let mut reseal = |path: &Path| -> LockerResult<String> {
+            let encrypted_data = fs::read(path).map_err(|e| {
+                SmartLockerError::FileSystemError(format!("Unable to read {:?}: {}", path, e))
+            })?;
+            let plaintext = open_secret(&source.name, &encrypted_data, &self.key_data)?;
+            let id = target.seal_new_file(to, &mut plaintext.expose(), cipher)?;
+            written.push(target.dir.join(format!("{}.slock", id)));
+            Ok(id)
+        };
+
+        let mut copy = source.clone();
+        copy.name = to.to_string();
+        for version in copy.history.iter_mut() {
+            version.id = reseal(&secret_version_path(&self.dir, version))?;
+        }
+        copy.id = reseal(&secret_file_path(&self.dir, source))?;
+        Ok(copy)
+    }
+
+    fn is_same_locker(&self, other: &Locker) -> bool {
+        match (fs::canonicalize(&self.dir), fs::canonicalize(&other.dir)) {
+            (Ok(dir), Ok(other_dir)) => dir == other_dir,
+            _ => self.dir == other.dir,
+        }
+    }
+
+    /// Encrypts a value bound to `name` into a new file, returning its identifier.
+    ///
+    /// The file is written under a temporary name, synced and renamed, so it is
+    /// either complete or absent.
+    fn seal_new_file<R: Read>(
+        &self,
+        name: &str,
+        reader: &mut R,
+        cipher: Cipher,
+    ) -> LockerResult<String> {
+        let id = generate_secret_id();
+        let output_path = self.dir.join(format!("{}.slock", id));
+        let tmp_path = self.dir.join(format!("{}.slock.tmp", id));
+        let sealed = create_private_file(&tmp_path)
+            .map_err(|e| {
+                SmartLockerError::FileSystemError(format!(
+                    "Error when writing encrypted file: {}",
+                    e
+                ))
+            })
+            .and_then(|file| seal_secret_stream(name, reader, file, &self.key_data, cipher))
+            .and_then(|file| {
+                file.sync_all()
+                    .and_then(|_| fs::rename(&tmp_path, &output_path))
+                    .and_then(|_| sync_parent_dir(&output_path))
+                    .map_err(|e| {
+                        SmartLockerError::FileSystemError(format!(
+                            "Error when writing encrypted file: {}",
+                            e
+                        ))
+                    })
+            });
+        match sealed {
+            Ok(()) => Ok(id),
+            Err(err) => {
+                let _ = fs::remove_file(&tmp_path);
+                Err(err)
+            }
+        }
+    }
+
+    /// Finds the file of a secret, or of one of its versions, after checking its metadata and expiration.
+    fn locate_secret(&self, name: &str, version: Option<u32>) -> LockerResult<LocatedSecret> {
+        validate_secret_name(name)?;
+        let metadata = self.read_index()?;
+
+        if !is_metadata_current(name, &metadata) {
+            return Err(SmartLockerError::DecryptionError(format!(
+                "Metadata for secret '{}' is missing or outdated. It must be migrated.",
+                name
+            )));
+        }
+        let secret_metadata = &metadata.secrets[name];
+        let (path, value_type) = match version {
+            Some(version) if version != secret_metadata.version => {
+                let previous = secret_metadata
+                    .history
+                    .iter()
+                    .find(|previous| previous.version == version)
+                    .ok_or_else(|| version_not_found(name, version))?;
+                (
+                    secret_version_path(&self.dir, previous),
+                    previous.value_type,
+                )
+            }
+            _ => (
+                secret_file_path(&self.dir, secret_metadata),
+                secret_metadata.value_type,
+            ),
+        };
+
+        // Vérifier si le secret est expiré
+        if is_secret_expired(secret_metadata) {
+            self.update_index(|metadata| {
+                if let Some(secret_metadata) = metadata.secrets.get_mut(name) {
+                    secret_metadata.expired = true;
+                }
+                Ok(())
+            })?;
+            return Err(self.expired(name));
+        }
+
+        Ok(LocatedSecret {
+            metadata,
+            path,
+            value_type,
+        })
+    }
+
+    /// Distinguishes a moved or renamed file from a corrupted one when decryption fails.
+    fn explain_failure(
+        &self,
+        err: SmartLockerError,
+        name: &str,
+        encrypted_data: &[u8],
+        secret: &LocatedSecret,
+    ) -> SmartLockerError {
+        if !matches!(err, SmartLockerError::DecryptionError(_)) {
+            return err;
+        }
+        let candidates = secret
+            .metadata
+            .secrets
+            .keys()
+            .filter(|other| *other != name);
+        match find_bound_name(
+            encrypted_data,
+            &self.key_data,
+            candidates.map(|s| s.as_str()),
+        ) {
+            Some(bound_name) => SmartLockerError::SecretMismatch(format!(
+                "The file of secret '{}' holds the secret '{}'. It was moved or renamed.",
+                name, bound_name
+            )),
+            None => err,
+        }
+    }
+
+    fn read_index(&self) -> LockerResult<MetadataFile> {
+        // Sceller un ancien `metadata.json` est une écriture : elle se fait sous verrou
+        if has_legacy_metadata(&self.dir) {
+            let _lock = lock_locker(&self.dir)?;
+            return self.read_index_locked();
+        }
+        read_metadata_in(&self.dir, &self.key_data)
+    }
+
+    /// Reads the index; the caller holds the lock of the locker.
+    fn read_index_locked(&self) -> LockerResult<MetadataFile> {
+        let legacy = has_legacy_metadata(&self.dir);
+        let metadata = read_metadata_in(&self.dir, &self.key_data)?;
+        if legacy {
+            self.emit(LockerEvent::LegacyMetadataSealed);
+        }
+        Ok(metadata)
+    }
+
+    fn write_index(&self, metadata: &MetadataFile) -> LockerResult<()> {
+        write_metadata_in(&self.dir, metadata, &self.key_data)
+    }
+
+    /// Reads, modifies and writes the index under the lock of the locker.
+    ///
+    /// The index is written even if `update` changed nothing, and not at all if it fails.
+    fn update_index<T>(
+        &self,
+        update: impl FnOnce(&mut MetadataFile) -> LockerResult<T>,
+    ) -> LockerResult<T> {
+        let _lock = lock_locker(&self.dir)?;
+        let mut metadata = self.read_index_locked()?;
+        let result = update(&mut metadata)?;
+        self.write_index(&metadata)?;
+        Ok(result)
+    }
+
+    /// Reports an expired secret and returns the error to give back.
+    fn expired(&self, name: &str) -> SmartLockerError {
+        self.emit(LockerEvent::SecretExpired(name.to_string()));
+        SmartLockerError::DecryptionError(format!(
+            "The secret '{}' has expired. Please renew it to use it again.",
+            name
+        ))
+    }
+
+    fn emit(&self, event: LockerEvent) {
+        if let Some(observer) = &self.observer {
+            observer(&event);
+        }
+    }
+}
+
+fn existing_locker_dir(path: &Path) -> LockerResult<PathBuf> {
+    if !path.join(KEY_FILE).exists() {
+        return Err(SmartLockerError::InitializationError(format!(
+            "No locker found in {:?}. Run `init` to create it.",
+            path
+        )));
+    }
+    Ok(path.to_path_buf())
+}
+
+fn target_exists(name: &str) -> SmartLockerError {
+    SmartLockerError::InitializationError(format!(
+        "A secret named '{}' already exists. Use --force to replace it.",
+        name
+    ))
+}
+
+/// Removes a secret from the index, unless it was written since `source` was read.
+fn take_unchanged(metadata: &mut MetadataFile, source: &SecretMetadata) -> LockerResult<()> {
+    if metadata.secrets.get(&source.name).map(|secret| &secret.id) != Some(&source.id) {
+        return Err(SmartLockerError::InitializationError(format!(
+            "The secret '{}' changed during the operation. Please try again.",
+            source.name
+        )));
+    }
+    metadata.secrets.remove(&source.name);
+    Ok(())
+}
+
+fn secret_not_found(name: &str) -> SmartLockerError {
+    SmartLockerError::DecryptionError(format!("Metadata for secret '{}' not found.", name))
+}
+
+fn version_not_found(name: &str, version: u32) -> SmartLockerError {
+    SmartLockerError::DecryptionError(format!(
+        "Version {} of secret '{}' not found. Run `history -n {}` to see the versions kept.",
+        version, name, name
+    ))
+}
+
+/// Drops the oldest versions beyond `keep_versions`, returning them.
+fn prune_history(history: &mut Vec<SecretVersion>, keep_versions: usize) -> Vec<SecretVersion> {
+    let excess = history.len().saturating_sub(keep_versions);
+    history.drain(..excess).collect()
+}
+
+/// Removes the file of a secret and the files of its previous versions.
+fn remove_secret_files(locker_dir: &Path, secret_metadata: &SecretMetadata) -> LockerResult<()> {
+    for version in secret_metadata.history() {
+        remove_secret_file(&secret_version_path(locker_dir, version))?;
+    }
+    remove_secret_file(&secret_file_path(locker_dir, secret_metadata))
+}
+
+fn remove_secret_file(path: &Path) -> LockerResult<()> {
+    if path.exists() {
+        fs::remove_file(path).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error when deleting the file: {}", e))
+        })?;
+    }
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Checks that the bytes going through a reader are valid UTF-8.
+struct Utf8Detector<R: Read> {
+    reader: R,
+    pending: Zeroizing<Vec<u8>>, // Début d'un caractère coupé entre deux lectures
+    valid: bool,
+}
+
+impl<R: Read> Utf8Detector<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            pending: Zeroizing::new(Vec::new()),
+            valid: true,
+        }
+    }
+
+    /// Text if everything read so far is valid UTF-8, binary otherwise.
+    fn value_type(&self) -> ValueType {
+        if self.valid && self.pending.is_empty() {
+            ValueType::Text
+        } else {
+            ValueType::Binary
+        }
+    }
+}
+
+impl<R: Read> Read for Utf8Detector<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.reader.read(buf)?;
+        if self.valid && len > 0 {
+            let mut data = Zeroizing::new(std::mem::take(&mut *self.pending));
+            data.extend_from_slice(&buf[..len]);
+            match std::str::from_utf8(&data) {
+                Ok(_) => {}
+                Err(e) if e.error_len().is_none() => {
+                    self.pending = Zeroizing::new(data[e.valid_up_to()..].to_vec());
+                }
+                Err(_) => self.valid = false,
+            }
+        }
+        Ok(len)
+    }
+}
@@ -0,0 +1,519 @@
+use super::{Locker, VaultIssue};
+use crate::utils::config::{LockerConfig, LOCKER_CONFIG_FILE};
+use crate::utils::crypto::{open_secret_stream, seal_secret_stream};
+use crate::utils::format::FormatError;
+use crate::utils::kdf::{derive_key, Kdf, KeyParams, KEY_PARAMS_FILE};
+use crate::utils::keyfile::{read_key_file, unlock_master_key, StoredKey, KEY_FILE};
+use crate::utils::lock::lock_locker;
+use crate::utils::metadata::{
+    find_unindexed_secret_files, indexed_secret_files, METADATA_INDEX_FILE,
+};
+use crate::utils::secret::SecretString;
+use crate::utils::toolbox::{
+    create_private_dir, create_private_file, sync_parent_dir, unique_tmp_path,
+};
+use crate::{LockerResult, SmartLockerError};
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{self, Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+const BACKUP_SIGNATURE: &[u8; 5] = b"SLBAK"; // Signature des archives `.slbak`
+const BACKUP_FORMAT_VERSION: u8 = 1; // Version du format des archives
+const BACKUP_AAD_NAME: &str = "\0smart-locker-backup"; // Nom authentifié du contenu, impossible pour un secret
+const MAX_PARAMS_SIZE: usize = 1024; // Taille maximale des paramètres de dérivation dans l'en-tête
+
+/// What to do with a secret of a backup that already exists in the locker it is restored into.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// Restore nothing if any secret already exists.
+    #[default]
+    Fail,
+    /// Keep the existing secret.
+    Skip,
+    /// Replace the existing secret, which is moved to the trash.
+    Overwrite,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "fail" => Ok(ConflictPolicy::Fail),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            _ => Err(format!("Unknown conflict policy '{}'", name)),
+        }
+    }
+}
+
+/// The secrets restored from a backup, sorted by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RestoreReport {
+    /// Secrets added to the locker.
+    pub restored: Vec<String>,
+    /// Secrets that replaced an existing one, moved to the trash.
+    pub replaced: Vec<String>,
+    /// Secrets left out because the locker already holds one with the same name.
+    pub skipped: Vec<String>,
+}
+
+impl Locker {
+    /// Writes an encrypted archive of the whole locker to `output`.
+    ///
+    /// The archive holds the wrapped key, the configuration, the index and every
+    /// secret file, with previous versions and the trash. It is encrypted and
+    /// authenticated under a key derived from `passphrase`, which must be the
+    /// passphrase of the locker: the same passphrase then unlocks the archive and
+    /// the key inside it.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - The number of secrets in the archive, outside the trash.
+    /// * `Err(SmartLockerError)` - If the passphrase is wrong, a file is missing or
+    ///   the archive cannot be written.
+    pub fn backup(&self, output: &Path, passphrase: &SecretString) -> LockerResult<usize> {
+        unlock_master_key(&self.dir, passphrase.expose())?;
+        let _lock = lock_locker(&self.dir)?;
+        let metadata = self.read_index_locked()?;
+
+        let mut files: Vec<PathBuf> = [KEY_FILE, KEY_PARAMS_FILE, LOCKER_CONFIG_FILE]
+            .iter()
+            .map(|file| self.dir.join(file))
+            .filter(|path| path.exists())
+            .collect();
+        files.push(self.dir.join(METADATA_INDEX_FILE));
+        files.extend(
+            indexed_secret_files(&self.dir, &metadata)
+                .into_iter()
+                .chain(find_unindexed_secret_files(&self.dir, &metadata)?)
+                .map(|(_, path)| path),
+        );
+        let mut entries = VecDeque::new();
+        for path in files {
+            if !path.exists() {
+                return Err(SmartLockerError::FileSystemError(format!(
+                    "The file {:?} is missing. Run `doctor` before backing up.",
+                    path
+                )));
+            }
+            entries.push_back(path);
+        }
+
+        // L'archive est protégée par le même KDF que la clé du locker
+        let kdf = match read_key_file(&self.dir)? {
+            StoredKey::Wrapped(key_file) => key_file.params.kdf,
+            StoredKey::Legacy(_) => Kdf::default(),
+        };
+        let params = KeyParams::generate(kdf);
+        let key = derive_key(passphrase.expose(), &params)?;
+        let params = serde_json::to_vec(&params).map_err(|e| {
+            SmartLockerError::EncryptionError(format!(
+                "Error serializing the key parameters: {}",
+                e
+            ))
+        })?;
+        let cipher = LockerConfig::load(&self.dir)?.cipher;
+
+        let file_name = output
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| {
+                SmartLockerError::FileSystemError(format!("Invalid file path {:?}", output))
+            })?;
+        let tmp_path = unique_tmp_path(output, file_name);
+        let write_error = |e: io::Error| {
+            SmartLockerError::FileSystemError(format!("Error writing {:?}: {}", output, e))
+        };
+        let written = create_private_file(&tmp_path)
+            .and_then(|mut file| {
+                file.write_all(BACKUP_SIGNATURE)?;
+                file.write_all(&[BACKUP_FORMAT_VERSION])?;
+                file.write_all(&(params.len() as u16).to_be_bytes())?;
+                file.write_all(&params)?;
+                Ok(file)
+            })
+            .map_err(write_error)
+            .and_then(|file| {
+                let mut archive = ArchiveReader::new(entries);
+                seal_secret_stream(BACKUP_AAD_NAME, &mut archive, file, &key, cipher)
+            })
+            .and_then(|file| {
+                file.sync_all()
+                    .and_then(|_| fs::rename(&tmp_path, output))
+                    .and_then(|_| sync_parent_dir(output))
+                    .map_err(write_error)
+            });
+        if let Err(err) = written {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(err);
+        }
+        Ok(metadata.secrets.len())
+    }
+
+    /// Creates a locker in `path` from a backup, as it was when the backup was made.
+    ///
+    /// The archive is unpacked and checked in a staging directory first: nothing
+    /// is written to the locker unless every secret decrypts.
+    ///
+    /// # Returns
+    /// * `Ok(Locker)` - The restored locker, unlocked with `passphrase`.
+    /// * `Err(SmartLockerError)` - `InitializationError` if a locker already exists in
+    ///   `path` (see [`Locker::import_backup`]), or if the archive is invalid.
+    pub fn restore_backup(
+        path: impl AsRef<Path>,
+        input: &Path,
+        passphrase: &SecretString,
+    ) -> LockerResult<Self> {
+        let dir = path.as_ref().to_path_buf();
+        create_private_dir(&dir).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error creating folder {:?}: {}", dir, e))
+        })?;
+
+        let lock = lock_locker(&dir)?;
+        if dir.join(KEY_FILE).exists() {
+            return Err(SmartLockerError::InitializationError(format!(
+                "A locker already exists in {:?}",
+                dir
+            )));
+        }
+        let staging = StagingDir::create(&dir)?;
+        let files = unpack_backup(input, passphrase, &staging.0)?.1;
+
+        // La clé est placée en dernier : sans elle, le dossier n'est pas un locker
+        let (key, others): (Vec<_>, Vec<_>) = files.into_iter().partition(|file| file == KEY_FILE);
+        for file in others.iter().chain(&key) {
+            fs::rename(staging.0.join(file), dir.join(file))
+                .and_then(|_| sync_parent_dir(&dir.join(file)))
+                .map_err(|e| {
+                    SmartLockerError::FileSystemError(format!(
+                        "Error restoring {:?}: {}",
+                        dir.join(file),
+                        e
+                    ))
+                })?;
+        }
+        // Une clé en clair restaurée est enveloppée à l'ouverture, hors du verrou
+        drop(lock);
+        Self::open_with_passphrase(&dir, passphrase)
+    }
+
+    /// Imports the secrets of a backup into this locker, with their metadata and versions.
+    ///
+    /// Each secret is re-encrypted under the key of this locker. The archive is
+    /// checked, and the conflicts are decided, before anything is imported;
+    /// secrets in the trash of the backup are left out. Secrets are then imported
+    /// one at a time: if one fails, those already imported stay in the locker.
+    ///
+    /// # Returns
+    /// * `Ok(RestoreReport)` - The secrets restored, replaced and skipped.
+    /// * `Err(SmartLockerError)` - If the archive is invalid, or if a secret already
+    ///   exists with [`ConflictPolicy::Fail`]: nothing was imported. If a secret
+    ///   fails to import, `RestoreInterrupted` carries the report of the secrets
+    ///   imported before it.
+    pub fn import_backup(
+        &self,
+        input: &Path,
+        passphrase: &SecretString,
+        on_conflict: ConflictPolicy,
+    ) -> LockerResult<RestoreReport> {
+        let staging = StagingDir::create(&self.dir)?;
+        let source = unpack_backup(input, passphrase, &staging.0)?.0;
+
+        let existing = {
+            let _lock = lock_locker(&self.dir)?;
+            self.read_index_locked()?.secrets
+        };
+        let names: Vec<String> = source
+            .list()?
+            .into_iter()
+            .map(|secret| secret.name)
+            .collect();
+        let conflicts: Vec<&str> = names
+            .iter()
+            .filter(|name| existing.contains_key(*name))
+            .map(|name| name.as_str())
+            .collect();
+        if on_conflict == ConflictPolicy::Fail && !conflicts.is_empty() {
+            return Err(SmartLockerError::InitializationError(format!(
+                "The backup holds secrets that already exist: {}. Choose to skip or overwrite them.",
+                conflicts.join(", ")
+            )));
+        }
+
+        let mut report = RestoreReport::default();
+        for name in names {
+            let replace = existing.contains_key(&name);
+            if replace && on_conflict == ConflictPolicy::Skip {
+                report.skipped.push(name);
+                continue;
+            }
+            // Un secret créé depuis est refusé par `transfer` sans être écrasé
+            if let Err(error) = source.transfer(self, &name, &name, replace, false) {
+                return Err(SmartLockerError::RestoreInterrupted {
+                    report,
+                    error: Box::new(error),
+                });
+            }
+            if replace {
+                report.replaced.push(name);
+            } else {
+                report.restored.push(name);
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Decrypts a backup into `staging` and checks that the locker it holds is complete.
+///
+/// Returns the unpacked locker and the names of its files.
+fn unpack_backup(
+    input: &Path,
+    passphrase: &SecretString,
+    staging: &Path,
+) -> LockerResult<(Locker, Vec<String>)> {
+    let mut file = fs::File::open(input).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Unable to read the backup {:?}: {}", input, e))
+    })?;
+    let read_error = |e: io::Error| match e.kind() {
+        io::ErrorKind::UnexpectedEof => {
+            SmartLockerError::DecryptionError("The backup is truncated".to_string())
+        }
+        _ => SmartLockerError::FileSystemError(format!("Unable to read the backup: {}", e)),
+    };
+
+    // En-tête : signature, version et paramètres de dérivation de la clé de l'archive
+    let mut header = [0u8; BACKUP_SIGNATURE.len() + 3];
+    file.read_exact(&mut header).map_err(read_error)?;
+    if &header[..BACKUP_SIGNATURE.len()] != BACKUP_SIGNATURE {
+        return Err(SmartLockerError::DecryptionError(format!(
+            "{:?} is not a smart-locker backup",
+            input
+        )));
+    }
+    let version = header[BACKUP_SIGNATURE.len()];
+    if version != BACKUP_FORMAT_VERSION {
+        return Err(FormatError::UnknownVersion(version).into());
+    }
+    let params_size = u16::from_be_bytes([header[6], header[7]]) as usize;
+    if params_size > MAX_PARAMS_SIZE {
+        return Err(SmartLockerError::DecryptionError(
+            "The backup header is corrupted".to_string(),
+        ));
+    }
+    let mut params = vec![0u8; params_size];
+    file.read_exact(&mut params).map_err(read_error)?;
+    let params: KeyParams = serde_json::from_slice(&params).map_err(|_| {
+        SmartLockerError::DecryptionError("The backup header is corrupted".to_string())
+    })?;
+    // L'en-tête n'est pas encore authentifié : borner le coût avant de dériver
+    params.validate()?;
+    let key = derive_key(passphrase.expose(), &params)?;
+
+    let mut unpacker = ArchiveWriter::new(staging);
+    open_secret_stream(BACKUP_AAD_NAME, &mut file, &mut unpacker, &key).map_err(
+        |err| match err {
+            SmartLockerError::DecryptionError(_) => SmartLockerError::DecryptionError(
+                "Unable to decrypt the backup: wrong passphrase or corrupted archive".to_string(),
+            ),
+            err => err,
+        },
+    )?;
+    let files = unpacker.finish()?;
+    for required in [KEY_FILE, METADATA_INDEX_FILE] {
+        if !files.iter().any(|file| file == required) {
+            return Err(SmartLockerError::DecryptionError(format!(
+                "The backup is incomplete: {} is missing",
+                required
+            )));
+        }
+    }
+
+    // Chaque secret doit se déchiffrer avec la clé de l'archive
+    let locker = Locker::open_with_passphrase(staging, passphrase)?;
+    let problems: Vec<String> = locker
+        .check()?
+        .iter()
+        .filter(|issue| {
+            matches!(
+                issue,
+                VaultIssue::MissingFile { .. } | VaultIssue::UnreadableFile { .. }
+            )
+        })
+        .map(|issue| issue.to_string())
+        .collect();
+    if !problems.is_empty() {
+        return Err(SmartLockerError::DecryptionError(format!(
+            "The backup is damaged: {}",
+            problems.join("; ")
+        )));
+    }
+    Ok((locker, files))
+}
+
+/// A private directory inside a locker, removed when dropped.
+struct StagingDir(PathBuf);
+
+impl StagingDir {
+    fn create(locker_dir: &Path) -> LockerResult<Self> {
+        let path = locker_dir.join(format!(
+            "restore.{}.tmp",
+            hex::encode(rand::random::<[u8; 8]>())
+        ));
+        create_private_dir(&path).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error creating folder {:?}: {}", path, e))
+        })?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Reads the files of a locker one after the other as a single archive.
+///
+/// Each file is `name length (u16) | name | size (u64) | content`, big endian.
+/// Files are opened only when their turn comes.
+struct ArchiveReader {
+    files: VecDeque<PathBuf>,
+    current: Option<Box<dyn Read>>,
+}
+
+impl ArchiveReader {
+    fn new(files: VecDeque<PathBuf>) -> Self {
+        Self {
+            files,
+            current: None,
+        }
+    }
+}
+
+impl Read for ArchiveReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(current) = &mut self.current {
+                let read = current.read(buf)?;
+                if read > 0 || buf.is_empty() {
+                    return Ok(read);
+                }
+            }
+            let Some(path) = self.files.pop_front() else {
+                return Ok(0);
+            };
+            let name = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .ok_or_else(|| io::Error::other(format!("Invalid file name {:?}", path)))?;
+            let file = fs::File::open(&path)?;
+            let size = file.metadata()?.len();
+
+            let mut entry = Vec::with_capacity(2 + name.len() + 8);
+            entry.extend_from_slice(&(name.len() as u16).to_be_bytes());
+            entry.extend_from_slice(name.as_bytes());
+            entry.extend_from_slice(&size.to_be_bytes());
+            self.current = Some(Box::new(Cursor::new(entry).chain(file.take(size))));
+        }
+    }
+}
+
+/// Writes the files of an archive into a directory as it is decrypted.
+struct ArchiveWriter {
+    dir: PathBuf,
+    header: Vec<u8>,
+    current: Option<(fs::File, u64)>,
+    files: Vec<String>,
+}
+
+impl ArchiveWriter {
+    fn new(dir: &Path) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            header: Vec::new(),
+            current: None,
+            files: Vec::new(),
+        }
+    }
+
+    /// Size of the header of the next file, once its name length is known.
+    fn header_size(&self) -> usize {
+        match self.header.get(..2) {
+            Some(len) => 2 + u16::from_be_bytes([len[0], len[1]]) as usize + 8,
+            None => 2,
+        }
+    }
+
+    fn start_file(&mut self) -> io::Result<()> {
+        let name_end = self.header.len() - 8;
+        let name = std::str::from_utf8(&self.header[2..name_end])
+            .ok()
+            .filter(|name| is_archive_file_name(name) && !self.files.iter().any(|f| f == name))
+            .ok_or_else(|| io::Error::other("invalid file name in the backup"))?
+            .to_string();
+        let size = u64::from_be_bytes(self.header[name_end..].try_into().unwrap());
+        let file = create_private_file(&self.dir.join(&name))?;
+        self.header.clear();
+        self.files.push(name);
+        if size == 0 {
+            file.sync_all()?;
+        } else {
+            self.current = Some((file, size));
+        }
+        Ok(())
+    }
+
+    /// Checks that the archive ended between two files and returns their names.
+    fn finish(self) -> LockerResult<Vec<String>> {
+        if self.current.is_some() || !self.header.is_empty() {
+            return Err(SmartLockerError::DecryptionError(
+                "The backup is truncated".to_string(),
+            ));
+        }
+        Ok(self.files)
+    }
+}
+
+impl Write for ArchiveWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut input = buf;
+        while !input.is_empty() {
+            if let Some((file, remaining)) = &mut self.current {
+                let len = (*remaining).min(input.len() as u64) as usize;
+                file.write_all(&input[..len])?;
+                *remaining -= len as u64;
+                input = &input[len..];
+                if *remaining == 0 {
+                    if let Some((file, _)) = self.current.take() {
+                        file.sync_all()?;
+                    }
+                }
+                continue;
+            }
+            let missing = (self.header_size() - self.header.len()).min(input.len());
+            self.header.extend_from_slice(&input[..missing]);
+            input = &input[missing..];
+            if self.header.len() >= 2 && self.header.len() == self.header_size() {
+                self.start_file()?;
+            }
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Only plain file names are accepted, so an archive cannot write outside the locker.
+fn is_archive_file_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
@@ -0,0 +1,479 @@
+use super::{remove_secret_file, Locker};
+use crate::commands::rotate::NEW_KEY_FILE;
+use crate::utils::crypto::open_secret_stream;
+use crate::utils::keyfile::ROTATION_JOURNAL;
+use crate::utils::lock::lock_locker;
+use crate::utils::metadata::{
+    find_unindexed_secret_files, indexed_secret_files, is_secret_expired, secret_file_path,
+    secret_version_path, SECRET_ID_SIZE,
+};
+use crate::utils::name::validate_secret_name;
+use crate::{LockerResult, MetadataFile, SecretMetadata, SmartLockerError};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+// Un fichier plus récent peut appartenir à une écriture en cours dans un autre processus
+const GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+const TEMP_EXTENSIONS: [&str; 2] = ["tmp", "rotating"]; // Écritures atomiques et staging de `rotate-key`
+
+/// A problem found in a locker by [`Locker::check`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VaultIssue {
+    /// A `.slock` file named after an identifier the index does not refer to.
+    OrphanFile(PathBuf),
+    /// A `<name>.slock` file written by an older version, missing from the index.
+    UnindexedSecret { name: String, path: PathBuf },
+    /// A version of a secret, in use or in the trash, whose file is missing.
+    MissingFile {
+        name: String,
+        version: u32,
+        path: PathBuf,
+        trashed: bool,
+    },
+    /// A file that does not parse or does not authenticate with the key of the locker.
+    UnreadableFile {
+        name: String,
+        version: u32,
+        path: PathBuf,
+        reason: String,
+    },
+    /// The locker directory or one of its files is accessible by other users.
+    LoosePermissions { path: PathBuf, mode: u32 },
+    /// The `expired` flag of a secret does not match its expiration date.
+    StaleExpiredFlag { name: String, expired: bool },
+    /// A temporary file left by an interrupted write, or the staging directory
+    /// of an interrupted restore.
+    LeftoverTempFile(PathBuf),
+    /// A key rotation was interrupted; running `rotate-key` again resumes it.
+    InterruptedRotation,
+}
+
+impl VaultIssue {
+    /// Describes what [`Locker::repair`] does about this problem, or `None` if
+    /// it must be fixed by hand.
+    pub fn fix(&self) -> Option<String> {
+        match self {
+            VaultIssue::OrphanFile(_) => Some("delete the file".to_string()),
+            VaultIssue::LeftoverTempFile(path) => Some(if path.is_dir() {
+                "delete the directory".to_string()
+            } else {
+                "delete the file".to_string()
+            }),
+            VaultIssue::UnindexedSecret { name, .. } => validate_secret_name(name)
+                .ok()
+                .map(|_| "add it to the index with default metadata".to_string()),
+            VaultIssue::MissingFile { .. } => Some(
+                "remove the version from the index, falling back to the previous one \
+                 (a secret with no version left is dropped)"
+                    .to_string(),
+            ),
+            VaultIssue::LoosePermissions { path, .. } => Some(if path.is_dir() {
+                "restrict the directory to its owner (0700)".to_string()
+            } else {
+                "restrict the file to its owner (0600)".to_string()
+            }),
+            VaultIssue::StaleExpiredFlag { expired: true, .. } => {
+                Some("clear the expired flag".to_string())
+            }
+            VaultIssue::StaleExpiredFlag { expired: false, .. } => {
+                Some("flag the secret as expired".to_string())
+            }
+            VaultIssue::UnreadableFile { .. } | VaultIssue::InterruptedRotation => None,
+        }
+    }
+}
+
+impl fmt::Display for VaultIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VaultIssue::OrphanFile(path) => {
+                write!(f, "Orphan file {:?}: no secret refers to it", path)
+            }
+            VaultIssue::UnindexedSecret { name, path } => {
+                write!(f, "Secret '{}' ({:?}) is not in the index", name, path)
+            }
+            VaultIssue::MissingFile {
+                name,
+                version,
+                path,
+                trashed,
+            } => write!(
+                f,
+                "Version {} of {}secret '{}' is missing its file {:?}",
+                version,
+                if *trashed { "trashed " } else { "" },
+                name,
+                path
+            ),
+            VaultIssue::UnreadableFile {
+                name,
+                version,
+                path,
+                reason,
+            } => write!(
+                f,
+                "Version {} of secret '{}' cannot be read from {:?}: {}",
+                version, name, path, reason
+            ),
+            VaultIssue::LoosePermissions { path, mode } => write!(
+                f,
+                "{:?} is accessible by other users (mode {:o})",
+                path, mode
+            ),
+            VaultIssue::StaleExpiredFlag {
+                name,
+                expired: true,
+            } => write!(
+                f,
+                "Secret '{}' is flagged as expired but is still valid",
+                name
+            ),
+            VaultIssue::StaleExpiredFlag {
+                name,
+                expired: false,
+            } => write!(
+                f,
+                "Secret '{}' has expired but is not flagged as expired",
+                name
+            ),
+            VaultIssue::LeftoverTempFile(path) if path.is_dir() => {
+                write!(f, "Leftover temporary directory {:?}", path)
+            }
+            VaultIssue::LeftoverTempFile(path) => write!(f, "Leftover temporary file {:?}", path),
+            VaultIssue::InterruptedRotation => write!(
+                f,
+                "A key rotation was interrupted. Run `rotate-key` to finish it."
+            ),
+        }
+    }
+}
+
+impl Locker {
+    /// Checks the integrity of the locker, without changing anything.
+    ///
+    /// The index is compared with the files on disk, every file is decrypted
+    /// with the key of the locker, and permissions, `expired` flags and
+    /// temporary files are inspected. Orphan and temporary files younger than
+    /// an hour are ignored: they may belong to a write in progress.
+    ///
+    /// # Returns
+    /// * `Ok(Vec<VaultIssue>)` - The problems found, empty for a healthy locker.
+    /// * `Err(SmartLockerError)` - If the locker cannot be read or its index does not decrypt.
+    pub fn check(&self) -> LockerResult<Vec<VaultIssue>> {
+        let _lock = lock_locker(&self.dir)?;
+        let metadata = self.read_index_locked()?;
+        let mut issues = Vec::new();
+
+        let mut secrets: Vec<&SecretMetadata> = metadata.secrets.values().collect();
+        secrets.sort_by(|a, b| a.name.cmp(&b.name));
+
+        // Fichiers attendus par l'index : absents ou illisibles
+        let indexed = secrets.iter().map(|secret| (*secret, false)).chain(
+            metadata
+                .trash
+                .iter()
+                .map(|trashed| (trashed.secret(), true)),
+        );
+        for (secret, trashed) in indexed {
+            for (version, path) in version_files(&self.dir, secret) {
+                if !path.exists() {
+                    issues.push(VaultIssue::MissingFile {
+                        name: secret.name.clone(),
+                        version,
+                        path,
+                        trashed,
+                    });
+                } else if let Err(err) = self.authenticate(&secret.name, &path) {
+                    issues.push(VaultIssue::UnreadableFile {
+                        name: secret.name.clone(),
+                        version,
+                        path,
+                        reason: err.to_string(),
+                    });
+                }
+            }
+        }
+
+        // Fichiers absents de l'index
+        let mut unindexed = find_unindexed_secret_files(&self.dir, &metadata)?;
+        unindexed.sort();
+        for (stem, path) in unindexed {
+            if metadata.secrets.get(&stem).is_some_and(|s| s.id.is_empty()) {
+                continue; // Ancien fichier d'un secret indexé, vérifié plus haut
+            }
+            if is_secret_id(&stem) {
+                if is_stale(&path) {
+                    issues.push(VaultIssue::OrphanFile(path));
+                }
+            } else {
+                issues.push(VaultIssue::UnindexedSecret { name: stem, path });
+            }
+        }
+
+        for secret in secrets {
+            let expired = is_secret_expired(secret);
+            if secret.expired != expired {
+                issues.push(VaultIssue::StaleExpiredFlag {
+                    name: secret.name.clone(),
+                    expired: secret.expired,
+                });
+            }
+        }
+
+        // Une rotation interrompue garde ses fichiers de staging jusqu'à sa reprise
+        let rotating = self.dir.join(ROTATION_JOURNAL).exists();
+        if rotating {
+            issues.push(VaultIssue::InterruptedRotation);
+        }
+        let (files, dirs) = locker_entries(&self.dir)?;
+        for path in files {
+            if let Some(mode) = loose_mode(&path) {
+                issues.push(VaultIssue::LoosePermissions {
+                    path: path.clone(),
+                    mode,
+                });
+            }
+            if is_temp_file(&path, rotating) && is_stale(&path) {
+                issues.push(VaultIssue::LeftoverTempFile(path));
+            }
+        }
+        // Une restauration interrompue laisse son dossier de staging, avec la clé et l'index de la sauvegarde
+        for path in dirs {
+            if path.extension().is_some_and(|ext| ext == "tmp") && is_stale(&path) {
+                issues.push(VaultIssue::LeftoverTempFile(path));
+            }
+        }
+        if let Some(mode) = loose_mode(&self.dir) {
+            issues.push(VaultIssue::LoosePermissions {
+                path: self.dir.clone(),
+                mode,
+            });
+        }
+        Ok(issues)
+    }
+
+    /// Applies the fixes described by [`VaultIssue::fix`] for the given problems.
+    ///
+    /// Each problem is checked again under the lock of the locker, so a file
+    /// indexed since [`Locker::check`] is never deleted. The index is written
+    /// once, before any file is removed; unindexed secrets are migrated last.
+    ///
+    /// # Returns
+    /// * `Ok(usize)` - The number of problems fixed.
+    /// * `Err(SmartLockerError)` - If the index or a file cannot be written.
+    pub fn repair(&self, issues: &[VaultIssue]) -> LockerResult<usize> {
+        let lock = lock_locker(&self.dir)?;
+        let mut metadata = self.read_index_locked()?;
+        let referenced: HashSet<PathBuf> = indexed_secret_files(&self.dir, &metadata)
+            .into_iter()
+            .map(|(_, path)| path)
+            .collect();
+
+        let mut fixed = 0;
+        let mut index_changed = false;
+        let mut files_to_remove = Vec::new();
+        let mut secrets_to_migrate = Vec::new();
+        for issue in issues {
+            match issue {
+                VaultIssue::OrphanFile(path) | VaultIssue::LeftoverTempFile(path) => {
+                    if path.exists() && !referenced.contains(path) {
+                        files_to_remove.push(path.clone());
+                        fixed += 1;
+                    }
+                }
+                VaultIssue::UnindexedSecret { name, .. } => {
+                    if validate_secret_name(name).is_ok() {
+                        secrets_to_migrate.push(name.clone());
+                    }
+                }
+                VaultIssue::MissingFile { name, trashed, .. } => {
+                    drop_missing_versions(&self.dir, &mut metadata, name, *trashed);
+                    index_changed = true;
+                    fixed += 1;
+                }
+                VaultIssue::LoosePermissions { path, .. } => {
+                    restrict_permissions(path)?;
+                    fixed += 1;
+                }
+                VaultIssue::StaleExpiredFlag { name, .. } => {
+                    if let Some(secret) = metadata.secrets.get_mut(name) {
+                        secret.expired = is_secret_expired(secret);
+                        index_changed = true;
+                        fixed += 1;
+                    }
+                }
+                VaultIssue::UnreadableFile { .. } | VaultIssue::InterruptedRotation => {}
+            }
+        }
+
+        if index_changed {
+            self.write_index(&metadata)?;
+        }
+        for path in files_to_remove {
+            if path.is_dir() {
+                fs::remove_dir_all(&path).map_err(|e| {
+                    SmartLockerError::FileSystemError(format!(
+                        "Error removing the directory {:?}: {}",
+                        path, e
+                    ))
+                })?;
+            } else {
+                remove_secret_file(&path)?;
+            }
+        }
+
+        // La migration prend elle-même le verrou
+        drop(lock);
+        for name in secrets_to_migrate {
+            self.migrate(Some(&name))?;
+            fixed += 1;
+        }
+        Ok(fixed)
+    }
+
+    /// Decrypts a file into nothing, to check that it parses and authenticates.
+    fn authenticate(&self, name: &str, path: &Path) -> LockerResult<u64> {
+        let mut file = fs::File::open(path).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Unable to read the encrypted file: {}", e))
+        })?;
+        open_secret_stream(name, &mut file, &mut io::sink(), &self.key_data)
+    }
+}
+
+/// Lists the versions of a secret with their files, the current one last.
+///
+/// A secret indexed before files were named after an identifier still uses `<name>.slock`.
+fn version_files(locker_dir: &Path, secret: &SecretMetadata) -> Vec<(u32, PathBuf)> {
+    let mut files: Vec<_> = secret
+        .history()
+        .iter()
+        .map(|version| (version.version(), secret_version_path(locker_dir, version)))
+        .collect();
+    files.push((secret.version(), current_file(locker_dir, secret)));
+    files
+}
+
+fn current_file(locker_dir: &Path, secret: &SecretMetadata) -> PathBuf {
+    if secret.id.is_empty() {
+        locker_dir.join(format!("{}.slock", secret.name))
+    } else {
+        secret_file_path(locker_dir, secret)
+    }
+}
+
+/// Removes the versions whose file is missing from a secret, in use or in the
+/// trash; the latest remaining version becomes current, and a secret with no
+/// version left is dropped.
+fn drop_missing_versions(
+    locker_dir: &Path,
+    metadata: &mut MetadataFile,
+    name: &str,
+    trashed: bool,
+) {
+    let keep = |secret: &mut SecretMetadata| {
+        secret
+            .history
+            .retain(|version| secret_version_path(locker_dir, version).exists());
+        if current_file(locker_dir, secret).exists() {
+            return true;
+        }
+        match secret.history.pop() {
+            Some(previous) => {
+                secret.id = previous.id;
+                secret.version = previous.version;
+                secret.created_at = previous.created_at;
+                secret.value_type = previous.value_type;
+                true
+            }
+            None => false,
+        }
+    };
+    if trashed {
+        metadata
+            .trash
+            .retain_mut(|trashed| trashed.secret.name != name || keep(&mut trashed.secret));
+    } else if let Some(secret) = metadata.secrets.get_mut(name) {
+        if !keep(secret) {
+            metadata.secrets.remove(name);
+        }
+    }
+}
+
+fn is_secret_id(stem: &str) -> bool {
+    stem.len() == SECRET_ID_SIZE * 2 && stem.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn is_temp_file(path: &Path, rotating: bool) -> bool {
+    let extension = path.extension().and_then(|ext| ext.to_str());
+    if rotating && (extension == Some("rotating") || path.ends_with(NEW_KEY_FILE)) {
+        return false;
+    }
+    extension.is_some_and(|ext| TEMP_EXTENSIONS.contains(&ext)) || path.ends_with(NEW_KEY_FILE)
+}
+
+/// Returns `true` if the file was last modified before the grace period.
+fn is_stale(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .map(|modified| {
+            SystemTime::now()
+                .duration_since(modified)
+                .is_ok_and(|age| age > GRACE_PERIOD)
+        })
+        .unwrap_or(false)
+}
+
+/// Lists the regular files and the directories directly in the locker directory.
+fn locker_entries(locker_dir: &Path) -> LockerResult<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let read_error = |e: io::Error| {
+        SmartLockerError::FileSystemError(format!("Error reading locker directory: {}", e))
+    };
+    let mut files = Vec::new();
+    let mut dirs = Vec::new();
+    for entry in fs::read_dir(locker_dir).map_err(read_error)? {
+        let entry = entry.map_err(read_error)?;
+        let file_type = entry.file_type().map_err(read_error)?;
+        if file_type.is_file() {
+            files.push(entry.path());
+        } else if file_type.is_dir() {
+            dirs.push(entry.path());
+        }
+    }
+    files.sort();
+    dirs.sort();
+    Ok((files, dirs))
+}
+
+/// Returns the mode of a file or directory that other users can access.
+#[cfg(unix)]
+fn loose_mode(path: &Path) -> Option<u32> {
+    use std::os::unix::fs::PermissionsExt;
+    let mode = fs::symlink_metadata(path).ok()?.permissions().mode() & 0o777;
+    (mode & 0o077 != 0).then_some(mode)
+}
+
+#[cfg(not(unix))]
+fn loose_mode(_path: &Path) -> Option<u32> {
+    None
+}
+
+fn restrict_permissions(path: &Path) -> LockerResult<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = if path.is_dir() { 0o700 } else { 0o600 };
+        fs::set_permissions(path, fs::Permissions::from_mode(mode)).map_err(|e| {
+            SmartLockerError::FileSystemError(format!(
+                "Error changing the permissions of {:?}: {}",
+                path, e
+            ))
+        })?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
@@ -2,17 +2,46 @@
 use clap::{Arg, Command};
 use colored::*; // For colored output
 use smart_locker::commands::{
-    decrypt::decrypt,
-    encrypt::encrypt,
+    backup::{backup_vault, restore_vault},
+    decrypt::{decrypt_bytes, decrypt_to_file, decrypt_version_bytes, decrypt_version_to_file},
+    doctor::{doctor, EXIT_FAILED},
+    encrypt::{encrypt_bytes, encrypt_file},
     export::export,
-    init::{backup_key, init_locker_with_passphrase, restore_key},
+    history::{rollback_secret, secret_history},
+    init::{
+        backup_key, init_locker_with_kdf, restore_key, set_locker_cipher, set_locker_keep_versions,
+    },
     list::list_secrets,
+    passwd::change_passphrase,
     remove::remove_secret,
     renew::renew_secret,
+    rotate::rotate_key,
+    transfer::{copy_secret, move_secret, rename_secret},
+    trash::{list_trash, purge_trash, restore_secret},
+    upgrade::upgrade_kdf,
+    vault::{create_vault, current_vault, list_vaults, remove_vault, set_default_vault},
 };
-use smart_locker::utils::toolbox::copy_to_clipboard;
-use std::io::Read;
+use smart_locker::utils::config::Cipher;
+use smart_locker::utils::interaction::{set_interaction, Interaction};
+use smart_locker::utils::kdf::{
+    Kdf, DEFAULT_ARGON2_ITERATIONS, DEFAULT_ARGON2_MEMORY_KIB, DEFAULT_ARGON2_PARALLELISM,
+    DEFAULT_PBKDF2_ITERATIONS,
+};
+use smart_locker::utils::passphrase::{
+    prompt_new_secret, read_new_passphrase, read_passphrase, set_passphrase_source,
+    PassphraseSource,
+};
+use smart_locker::utils::toolbox::{
+    copy_to_clipboard, parse_duration, strip_trailing_newline, trim_whitespace,
+};
+use smart_locker::utils::vault::{select_vault, VaultSelection, LOCAL_VAULT_DIR};
+use smart_locker::{
+    ConflictPolicy, RestoreReport, SecretBytes, SecretString, SmartLockerError, ValueType,
+};
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
 use std::process::exit;
+use zeroize::Zeroizing;
 
 fn main() {
     // Display the logo only for general help
@@ -29,30 +58,120 @@ fn main() {
         decrypt, list, and delete sensitive secrets.\n\n\
         Available commands:\n\
         - init: Initializes the vault and generates a symmetric key.\n\
-            --passphrase: Passphrase to generate the symmetric key.\n\
+            --local: Creates the vault in ./.smart-locker for the current project.\n\
+            --passphrase: Passphrase protecting the symmetric key.\n\
+            --kdf: Key-derivation function (argon2id or pbkdf2, default: argon2id).\n\
+            --cipher: Cipher for new secrets (aes-256-gcm, chacha20-poly1305, xchacha20-poly1305).\n\
+        - passwd: Changes the passphrase protecting the vault key.\n\
+        - rotate-key: Generates a new vault key and re-encrypts every secret.\n\
+        - upgrade-kdf: Re-wraps the vault key with an Argon2id-derived key.\n\
+            --passphrase: Passphrase protecting the vault.\n\
         - encrypt: Encrypts a secret and saves it in the vault.\n\
-            --name: Name of the secret.\n\
+            --name: Name of the secret, with optional namespaces (e.g., prod/db/password).\n\
             --value: Value of the secret to encrypt.\n\
             --tags: Comma-separated tags for the secret (e.g., tag1,tag2).\n\
             --expiration: Expiration date in days (default: 15).\n\
             If --value is not provided, the value will be read from stdin.\n\n\
         - decrypt: Decrypts a secret.\n\
             --name: Name of the secret to decrypt.\n\
-            --clipboard: Copies the decrypted secret to the clipboard.\n\n\
+            --clipboard: Copies the decrypted secret to the clipboard.\n\
+            --version: Decrypts a previous version of the secret.\n\n\
+        - history: Lists the versions kept for a secret.\n\
+        - rollback: Makes a previous version the current value of a secret.\n\
+            --to: Version to restore.\n\n\
         - list: Lists all available secrets.\n\
-        - remove: Deletes a secret.\n\
-            --name: Name of the secret to delete.\n\n\
-            --all: Deletes all secrets.\n\n\
+            --prefix: Only lists the secrets of a namespace (e.g., prod/).\n\
+        - remove: Moves a secret to the trash.\n\
+            --name: Name of the secret to delete.\n\
+            --all: Deletes all secrets (asks for confirmation).\n\
+            --force: Deletes all secrets without confirmation.\n\n\
+        - restore: Restores a secret from the trash, or a vault from a backup.\n\
+            --input: Backup file to restore.\n\
+            --on-conflict: fail (default), skip or overwrite existing secrets.\n\
+        - rename / copy: Renames or copies a secret with its metadata and versions.\n\
+            --to: New name of the secret.\n\
+        - move: Moves a secret to another vault.\n\
+            --to-vault: Name of the target vault.\n\
+            --force: Replaces an existing secret with the same name.\n\
+        - trash: Lists (list) or deletes for good (purge --older-than 30d) removed secrets.\n\n\
         - renew: Renews an expired secret.\n\
             --name: Name of the secret to renew.\n\
             --days: Number of additional days to extend the expiration (default: 15).\n\n\
+        - doctor: Checks the integrity of the vault.\n\
+            --fix: Applies the plan to fix the problems found (asks for confirmation).\n\n\
+        - backup: Backs up the whole vault into one encrypted file.\n\
+            --output: Path of the backup file (e.g., vault.slbak).\n\
         - backup-key: Creates a backup of the encryption key.\n\
         - restore-key: Restores the encryption key from a backup.\n\n\
         - export: Exports secrets to a file in a specified format.\n\
             --format: Format to export secrets (e.g., env).\n\
             --output: Output file path (default: .env).\n\n\
+        - vault: Manages named vaults (create, list, current, remove, default).\n\n\
+        Global options:\n\
+            --vault: Name of the vault to use (or SMART_LOCKER_VAULT).\n\
+            --locker-dir: Directory of the vault to use.\n\
+            --passphrase-env: Environment variable holding the vault passphrase.\n\
+            --passphrase-fd: File descriptor to read the vault passphrase from.\n\
+            Without them, SMART_LOCKER_PASSPHRASE is used if set, otherwise the passphrase is prompted.\n\
+            --yes / --no: Answer every confirmation without asking.\n\
+            --non-interactive: Never prompt (for CI). Confirmations are also declined when stdin is not a terminal.\n\n\
         Use --help or -h after a command for more details.",
         )
+        .arg(
+            Arg::new("vault")
+                .long("vault")
+                .num_args(1)
+                .global(true)
+                .conflicts_with("locker-dir")
+                .help("Name of the vault to use (default: SMART_LOCKER_VAULT, then the default vault)"),
+        )
+        .arg(
+            Arg::new("locker-dir")
+                .long("locker-dir")
+                .num_args(1)
+                .global(true)
+                .help("Directory of the vault to use"),
+        )
+        .arg(
+            Arg::new("passphrase-env")
+                .long("passphrase-env")
+                .num_args(1)
+                .global(true)
+                .conflicts_with("passphrase-fd")
+                .help("Read the vault passphrase from this environment variable"),
+        )
+        .arg(
+            Arg::new("passphrase-fd")
+                .long("passphrase-fd")
+                .num_args(1)
+                .global(true)
+                .value_parser(clap::value_parser!(i32))
+                .help("Read the vault passphrase from this file descriptor"),
+        )
+        .arg(
+            Arg::new("yes")
+                .short('y')
+                .long("yes")
+                .action(clap::ArgAction::SetTrue)
+                .global(true)
+                .conflicts_with_all(["no", "non-interactive"])
+                .help("Answer yes to every confirmation"),
+        )
+        .arg(
+            Arg::new("no")
+                .long("no")
+                .action(clap::ArgAction::SetTrue)
+                .global(true)
+                .conflicts_with("non-interactive")
+                .help("Answer no to every confirmation"),
+        )
+        .arg(
+            Arg::new("non-interactive")
+                .long("non-interactive")
+                .action(clap::ArgAction::SetTrue)
+                .global(true)
+                .help("Never prompt: decline confirmations and fail instead of asking for a passphrase or a value"),
+        )
         .subcommand(
             Command::new("backup-key")
                 .about("Creates a backup of the encryption key")
@@ -63,6 +182,27 @@ fn main() {
                   smart-locker backup-key",
                 ),
         )
+        .subcommand(
+            Command::new("backup")
+                .about("Backs up the whole vault into one encrypted file")
+                .long_about(
+                    "Writes the key, configuration, index and every secret of the vault, with \
+                previous versions and the trash, into a single archive encrypted and \
+                authenticated under the passphrase of the vault. Keep it on another disk: it is \
+                all `restore --input` needs to bring the vault back.\n\n\
+                EXAMPLES:\n\
+                - Back up the vault:\n\
+                  smart-locker backup --output /mnt/usb/vault.slbak",
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .num_args(1)
+                        .required(true)
+                        .help("Path of the backup file (e.g., vault.slbak)"),
+                ),
+        )
         .subcommand(
             Command::new("restore-key")
                 .about("Restores the encryption key from a backup")
@@ -77,12 +217,25 @@ fn main() {
             Command::new("init")
                 .about("Initializes the vault and generates a symmetric key")
                 .long_about(
-                    "Initializes the vault by generating a symmetric key.\n\n\
+                    "Initializes the vault by generating a random symmetric key. The key is \
+                stored wrapped under a key derived from your passphrase, never in clear text.\n\n\
                 EXAMPLES:\n\
-                - Generate a random key:\n\
+                - Initialize the vault (the passphrase is prompted):\n\
                   smart-locker init\n\
-                - Generate a key from a passphrase:\n\
-                  smart-locker init --passphrase \"my passphrase\"",
+                - Initialize the vault with a passphrase:\n\
+                  smart-locker init --passphrase \"my passphrase\"\n\
+                - Encrypt new secrets with XChaCha20-Poly1305:\n\
+                  smart-locker init --cipher xchacha20-poly1305\n\
+                - Keep the 10 previous values of each secret:\n\
+                  smart-locker init --keep-versions 10\n\
+                - Create a vault for the current project in ./.smart-locker:\n\
+                  smart-locker init --local",
+                )
+                .arg(
+                    Arg::new("local")
+                        .long("local")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Create the vault in ./.smart-locker, used by commands run in this directory tree"),
                 )
                 .arg(
                     Arg::new("passphrase")
@@ -90,21 +243,112 @@ fn main() {
                         .long("passphrase")
                         .num_args(1)
                         .required(false)
-                        .help("Passphrase to generate the symmetric key"),
+                        .help("Passphrase protecting the symmetric key (prompted twice if omitted)"),
+                )
+                .arg(
+                    Arg::new("kdf")
+                        .long("kdf")
+                        .num_args(1)
+                        .value_parser(["argon2id", "pbkdf2"])
+                        .default_value("argon2id")
+                        .help("Key-derivation function used with --passphrase"),
+                )
+                .arg(
+                    Arg::new("iterations")
+                        .long("iterations")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Number of iterations (PBKDF2) or passes (Argon2id)"),
+                )
+                .args(argon2_args())
+                .arg(
+                    Arg::new("cipher")
+                        .long("cipher")
+                        .num_args(1)
+                        .value_parser(Cipher::ALL.map(|cipher| cipher.name()))
+                        .help("Cipher used for new secrets (default: aes-256-gcm)"),
+                )
+                .arg(
+                    Arg::new("keep-versions")
+                        .long("keep-versions")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(usize))
+                        .help("Number of previous versions kept for each secret (default: 5, 0 disables the history)"),
+                ),
+        )
+        .subcommand(
+            Command::new("passwd")
+                .about("Changes the passphrase protecting the vault key")
+                .long_about(
+                    "Re-wraps the vault key under a new passphrase. Only locker.key is rewritten; \
+                secrets are not re-encrypted.\n\n\
+                The current passphrase is read like for any other command, the new one from \
+                SMART_LOCKER_NEW_PASSPHRASE or prompted.\n\n\
+                EXAMPLES:\n\
+                - Change the passphrase:\n\
+                  smart-locker passwd",
+                ),
+        )
+        .subcommand(
+            Command::new("rotate-key")
+                .about("Generates a new vault key and re-encrypts every secret")
+                .long_about(
+                    "Generates a new data key and re-encrypts every secret under it with fresh \
+                nonces, using the cipher configured for the vault. Secrets are staged and \
+                verified before the old key is replaced. If the rotation is interrupted, run the \
+                command again to resume it.\n\n\
+                EXAMPLES:\n\
+                - Rotate the vault key:\n\
+                  smart-locker rotate-key",
                 ),
         )
+        .subcommand(
+            Command::new("upgrade-kdf")
+                .about("Upgrades the key derivation of a passphrase vault to Argon2id")
+                .long_about(
+                    "Re-wraps the vault key under a key derived from the passphrase with Argon2id \
+                and a new random salt. Secrets are not re-encrypted.\n\n\
+                EXAMPLES:\n\
+                - Upgrade a PBKDF2 vault to Argon2id:\n\
+                  smart-locker upgrade-kdf --passphrase \"my passphrase\"\n\
+                - Upgrade with a higher memory cost:\n\
+                  smart-locker upgrade-kdf --passphrase \"my passphrase\" --memory 65536",
+                )
+                .arg(
+                    Arg::new("passphrase")
+                        .short('p')
+                        .long("passphrase")
+                        .num_args(1)
+                        .required(false)
+                        .help("Passphrase protecting the vault (prompted if omitted)"),
+                )
+                .arg(
+                    Arg::new("iterations")
+                        .long("iterations")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Number of Argon2id passes"),
+                )
+                .args(argon2_args()),
+        )
         .subcommand(
             Command::new("encrypt")
                 .about("Encrypts a secret")
                 .long_about(
                     "Encrypts a secret and saves it in the vault.\n\n\
                 EXAMPLES:\n\
-                - Encrypt a secret with a value:\n\
+                - Encrypt a secret typed at a hidden prompt (asked twice):\n\
+                  smart-locker encrypt -n my_secret\n\
+                - Encrypt a secret with a value (visible in the shell history):\n\
                   smart-locker encrypt -n my_secret -v \"my value\"\n\
                 - Encrypt a secret with tags:\n\
                   smart-locker encrypt -n my_secret -v \"my value\" --tags \"tag1,tag2\"\n\
-                - Encrypt a secret by reading the value from stdin:\n\
-                  echo \"my value\" | smart-locker encrypt -n my_secret,
+                - Encrypt the exact bytes read from stdin:\n\
+                  cat key.pem | smart-locker encrypt -n my_key\n\
+                - Encrypt a value from stdin without the newline added by echo:\n\
+                  echo \"my value\" | smart-locker encrypt -n my_secret --strip-newline\n\
+                - Encrypt a binary file (certificate, keystore...):\n\
+                  smart-locker encrypt -n tls_cert --file cert.der\n\
                 - Encrypt a secret with an expiration of 30 days:\n\
                   smart-locker encrypt -n my_secret -v \"my value\" --expiration 30\n"
                 )
@@ -114,7 +358,7 @@ fn main() {
                         .long("name")
                         .num_args(1)
                         .required(true)
-                        .help("Name of the secret"),
+                        .help("Name of the secret: letters, digits, '_', '-', '.', namespaces separated by '/' (e.g., prod/db/password)"),
                 )
                 .arg(
                     Arg::new("value")
@@ -122,7 +366,30 @@ fn main() {
                         .long("value")
                         .num_args(1)
                         .required(false)
-                        .help("Value of the secret to encrypt"),
+                        .help("Value of the secret to encrypt (visible in the shell history; omit it to be prompted)"),
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .num_args(1)
+                        .required(false)
+                        .conflicts_with("value")
+                        .help("Reads the secret from a file, byte for byte"),
+                )
+                .arg(
+                    Arg::new("trim")
+                        .long("trim")
+                        .action(clap::ArgAction::SetTrue)
+                        .required(false)
+                        .help("Removes leading and trailing whitespace from the secret"),
+                )
+                .arg(
+                    Arg::new("strip-newline")
+                        .long("strip-newline")
+                        .action(clap::ArgAction::SetTrue)
+                        .required(false)
+                        .conflicts_with("trim")
+                        .help("Removes a single trailing newline (\\n or \\r\\n) from the secret"),
                 )
                 .arg(
                     Arg::new("tags")
@@ -151,7 +418,20 @@ fn main() {
                 - Decrypt a secret and display it:\n\
                   smart-locker decrypt -n my_secret\n\
                 - Decrypt a secret and copy it to the clipboard:\n\
-                  smart-locker decrypt -n my_secret --clipboard",
+                  smart-locker decrypt -n my_secret --clipboard\n\
+                - Write a binary secret to a file:\n\
+                  smart-locker decrypt -n tls_cert --output cert.der\n\
+                - Pipe the exact bytes of a secret to another program:\n\
+                  smart-locker decrypt -n my_token | base64\n\
+                - Decrypt a previous version (see `history`):\n\
+                  smart-locker decrypt -n my_secret --version 3",
+                )
+                .arg(
+                    Arg::new("version")
+                        .long("version")
+                        .num_args(1)
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Decrypts this version of the secret instead of the current one"),
                 )
                 .arg(
                     Arg::new("name")
@@ -168,6 +448,23 @@ fn main() {
                         .action(clap::ArgAction::SetTrue)
                         .required(false)
                         .help("Copies the decrypted secret to the clipboard"),
+                )
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .num_args(1)
+                        .required(false)
+                        .conflicts_with("clipboard")
+                        .help("Writes the decrypted secret to a file, byte for byte"),
+                )
+                .arg(
+                    Arg::new("raw")
+                        .long("raw")
+                        .action(clap::ArgAction::SetTrue)
+                        .required(false)
+                        .conflicts_with_all(["clipboard", "output"])
+                        .help("Writes the exact bytes of the secret to stdout, without color or newline (default when stdout is not a terminal)"),
                 ),
         )
         .subcommand(
@@ -177,19 +474,30 @@ fn main() {
                     "Displays the list of available secrets in the vault.\n\n\
                 EXAMPLES:\n\
                 - List all secrets:\n\
-                  smart-locker list",
+                  smart-locker list\n\
+                - List the secrets of a namespace:\n\
+                  smart-locker list --prefix prod/",
+                )
+                .arg(
+                    Arg::new("prefix")
+                        .short('p')
+                        .long("prefix")
+                        .num_args(1)
+                        .required(false)
+                        .help("Only lists the secrets whose name starts with this prefix (e.g., prod/)"),
                 ),
         )
         .subcommand(
             Command::new("remove")
-                .about("Deletes a secret")
+                .about("Moves a secret to the trash")
                 .long_about(
-                    "Deletes a secret from the vault.\n\n\
+                    "Moves a secret and its versions to the trash. It can be restored with \
+                `restore` until the trash is purged.\n\n\
                 EXAMPLES:\n\
                 - Delete a secret:\n\
-                  smart-locker remove -n my_secret,
-                - Delete all secrets:\n\
-                  smart-locker remove --all",
+                  smart-locker remove -n my_secret\n\
+                - Delete all secrets, without confirmation:\n\
+                  smart-locker remove --all --force",
                 )
                 .arg(
                     Arg::new("name")
@@ -205,6 +513,148 @@ fn main() {
                         .action(clap::ArgAction::SetTrue)
                         .conflicts_with("name")
                         .help("Remove all secrets and their metadata"),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .action(clap::ArgAction::SetTrue)
+                        .requires("all")
+                        .help("Removes all secrets without asking for confirmation"),
+                ),
+        )
+        .subcommand(
+            Command::new("restore")
+                .about("Restores a secret from the trash, or a vault from a backup")
+                .long_about(
+                    "With --name, brings the most recently removed secret with this name back \
+                from the trash, with its versions.\n\n\
+                With --input, checks a backup made by `backup` and restores it. A missing vault \
+                is recreated as it was; otherwise the secrets of the backup are re-encrypted \
+                into the vault, and --on-conflict decides what happens to those that already \
+                exist: fail (default, nothing is restored), skip, or overwrite (the existing \
+                secret is moved to the trash).\n\n\
+                EXAMPLES:\n\
+                - Restore a removed secret:\n\
+                  smart-locker restore -n my_secret\n\
+                - Restore a backup, keeping the secrets that already exist:\n\
+                  smart-locker restore --input vault.slbak --on-conflict skip\n\
+                - Restore a backup made before the vault passphrase changed, in a script:\n\
+                  smart-locker restore --input vault.slbak --backup-passphrase-env OLD_PASSPHRASE",
+                )
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .num_args(1)
+                        .required_unless_present("input")
+                        .conflicts_with("input")
+                        .help("Name of the secret to restore"),
+                )
+                .arg(
+                    Arg::new("input")
+                        .short('i')
+                        .long("input")
+                        .num_args(1)
+                        .help("Backup file to restore"),
+                )
+                .arg(
+                    Arg::new("on-conflict")
+                        .long("on-conflict")
+                        .num_args(1)
+                        .requires("input")
+                        .value_parser(["fail", "skip", "overwrite"])
+                        .default_value("fail")
+                        .help("What to do with secrets of the backup that already exist"),
+                )
+                .arg(
+                    Arg::new("backup-passphrase-env")
+                        .long("backup-passphrase-env")
+                        .num_args(1)
+                        .requires("input")
+                        .help(
+                            "Read the backup passphrase from this environment variable, \
+                             when it differs from the vault passphrase",
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("rename")
+                .about("Renames a secret")
+                .long_about(
+                    "Renames a secret, keeping its creation date, tags, expiration and versions.\n\n\
+                EXAMPLES:\n\
+                - Rename a secret:\n\
+                  smart-locker rename -n db_password --to prod/db/password",
+                )
+                .args(transfer_args("Name of the secret to rename"))
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .num_args(1)
+                        .required(true)
+                        .help("New name of the secret"),
+                ),
+        )
+        .subcommand(
+            Command::new("copy")
+                .about("Copies a secret under another name")
+                .long_about(
+                    "Copies a secret under another name, with its creation date, tags, \
+                expiration and versions.\n\n\
+                EXAMPLES:\n\
+                - Copy a secret:\n\
+                  smart-locker copy -n prod/api_key --to staging/api_key",
+                )
+                .args(transfer_args("Name of the secret to copy"))
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .num_args(1)
+                        .required(true)
+                        .help("Name of the copy"),
+                ),
+        )
+        .subcommand(
+            Command::new("move")
+                .about("Moves a secret to another vault")
+                .long_about(
+                    "Moves a secret to a named vault, with its metadata and versions. The \
+                secret is re-encrypted with the key of the target vault.\n\n\
+                EXAMPLES:\n\
+                - Move a secret from the current vault to the vault 'prod':\n\
+                  smart-locker move -n api_key --to-vault prod",
+                )
+                .args(transfer_args("Name of the secret to move"))
+                .arg(
+                    Arg::new("to-vault")
+                        .long("to-vault")
+                        .num_args(1)
+                        .required(true)
+                        .help("Name of the target vault"),
+                ),
+        )
+        .subcommand(
+            Command::new("trash")
+                .about("Lists or purges removed secrets")
+                .long_about(
+                    "Removed secrets stay encrypted in the trash until it is purged.\n\n\
+                EXAMPLES:\n\
+                - List the removed secrets:\n\
+                  smart-locker trash list\n\
+                - Delete for good the secrets removed more than 30 days ago:\n\
+                  smart-locker trash purge --older-than 30d",
+                )
+                .subcommand_required(true)
+                .subcommand(Command::new("list").about("Lists the removed secrets"))
+                .subcommand(
+                    Command::new("purge")
+                        .about("Deletes removed secrets for good")
+                        .arg(
+                            Arg::new("older-than")
+                                .long("older-than")
+                                .num_args(1)
+                                .help("Only purges the secrets removed before this duration (e.g., 30d, 12h, 2w)"),
+                        ),
                 ),
         )
         .subcommand(
@@ -235,6 +685,53 @@ fn main() {
                         .help("Output file path (default: .env)"),
                 ),
         )
+        .subcommand(
+            Command::new("history")
+                .about("Lists the versions kept for a secret")
+                .long_about(
+                    "Lists the versions kept for a secret, oldest first. Each encryption of an \
+                existing secret keeps its previous value, up to the number set with \
+                `init --keep-versions`.\n\n\
+                EXAMPLES:\n\
+                - Show the versions of a secret:\n\
+                  smart-locker history -n my_secret",
+                )
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .num_args(1)
+                        .required(true)
+                        .help("Name of the secret"),
+                ),
+        )
+        .subcommand(
+            Command::new("rollback")
+                .about("Makes a previous version the current value of a secret")
+                .long_about(
+                    "Restores a previous version of a secret as a new version, so the value \
+                being replaced stays in the history.\n\n\
+                EXAMPLES:\n\
+                - Restore version 3 of a secret:\n\
+                  smart-locker rollback -n my_secret --to 3",
+                )
+                .arg(
+                    Arg::new("name")
+                        .short('n')
+                        .long("name")
+                        .num_args(1)
+                        .required(true)
+                        .help("Name of the secret"),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .num_args(1)
+                        .required(true)
+                        .value_parser(clap::value_parser!(u32))
+                        .help("Version to restore"),
+                ),
+        )
         .subcommand(
             Command::new("renew")
                 .about("Renews an expired secret")
@@ -263,34 +760,156 @@ fn main() {
                         .default_value("15")
                         .help("Number of additional days to extend the expiration"),
                 ),
-        ).get_matches();
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Checks the integrity of the vault")
+                .long_about(
+                    "Checks the vault for orphan files and index entries, files that do not \
+                decrypt with the vault key, permissions open to other users, stale expired \
+                flags and leftover temporary files, and prints a plan to fix them.\n\n\
+                Exit codes: 0 when no problem is found, 1 when every problem was fixed, 2 when \
+                problems remain, 3 when the vault cannot be checked.\n\n\
+                EXAMPLES:\n\
+                - Check the vault:\n\
+                  smart-locker doctor\n\
+                - Fix the problems found, from a cron job:\n\
+                  smart-locker --yes doctor --fix",
+                )
+                .arg(
+                    Arg::new("fix")
+                        .long("fix")
+                        .action(clap::ArgAction::SetTrue)
+                        .help("Applies the plan to fix the problems found"),
+                ),
+        )
+        .subcommand(
+            Command::new("vault")
+                .about("Manages named vaults")
+                .long_about(
+                    "Manages named vaults, for instance to keep personal, staging and production \
+                secrets apart. The vault named 'default' is ~/.locker. Select a vault for any \
+                command with --vault NAME or SMART_LOCKER_VAULT.\n\n\
+                EXAMPLES:\n\
+                - Create a vault in ~/.locker/vaults/staging:\n\
+                  smart-locker vault create staging\n\
+                - Create a vault in a given directory:\n\
+                  smart-locker vault create prod --path /secure/prod-locker\n\
+                - Use a vault for one command:\n\
+                  smart-locker --vault staging list\n\
+                - Make it the default vault:\n\
+                  smart-locker vault default staging",
+                )
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("create")
+                        .about("Creates a vault and registers it")
+                        .arg(
+                            Arg::new("name")
+                                .required(true)
+                                .help("Name of the vault (letters, digits, '-' and '_')"),
+                        )
+                        .arg(
+                            Arg::new("path")
+                                .long("path")
+                                .num_args(1)
+                                .help("Directory of the vault (default: ~/.locker/vaults/NAME)"),
+                        ),
+                )
+                .subcommand(Command::new("list").about("Lists the vaults"))
+                .subcommand(
+                    Command::new("current").about("Shows which vault is used from here, and why"),
+                )
+                .subcommand(
+                    Command::new("remove")
+                        .about("Removes a vault from the registry, keeping its files")
+                        .arg(Arg::new("name").required(true).help("Name of the vault")),
+                )
+                .subcommand(
+                    Command::new("default")
+                        .about("Sets the vault used when none is selected")
+                        .arg(Arg::new("name").required(true).help("Name of the vault")),
+                ),
+        )
+        .get_matches();
+
+    // Sans terminal sur stdin, les confirmations sont refusées plutôt que de consommer l'entrée
+    set_interaction(if matches.get_flag("yes") {
+        Interaction::AssumeYes
+    } else if matches.get_flag("no") {
+        Interaction::AssumeNo
+    } else if matches.get_flag("non-interactive") {
+        Interaction::NonInteractive
+    } else if std::io::stdin().is_terminal() {
+        Interaction::Prompt
+    } else {
+        Interaction::AssumeNo
+    });
+
+    if let Some(name) = matches.get_one::<String>("vault") {
+        select_vault(VaultSelection::Named(name.clone()));
+    } else if let Some(dir) = matches.get_one::<String>("locker-dir") {
+        select_vault(VaultSelection::Path(dir.into()));
+    }
+
+    if let Some(var) = matches.get_one::<String>("passphrase-env") {
+        set_passphrase_source(PassphraseSource::Env(var.clone()));
+    } else if let Some(fd) = matches.get_one::<i32>("passphrase-fd") {
+        set_passphrase_source(PassphraseSource::Fd(*fd));
+    }
 
     if let Some(matches) = matches.subcommand_matches("init") {
         display_logo(); // Affiche le logo uniquement pour la commande init
 
+        // Un vault de projet est créé dans le dossier courant
+        if matches.get_flag("local") {
+            if matches.get_one::<String>("vault").is_some()
+                || matches.get_one::<String>("locker-dir").is_some()
+            {
+                eprintln!(
+                    "{}",
+                    "--local cannot be used with --vault or --locker-dir".red()
+                );
+                exit(1);
+            }
+            let local_dir = std::env::current_dir()
+                .expect("Unable to get current directory")
+                .join(LOCAL_VAULT_DIR);
+            select_vault(VaultSelection::Path(local_dir));
+        }
+
         // Récupérer la passphrase si elle est fournie
         let passphrase = matches.get_one::<String>("passphrase").map(|s| s.as_str());
+        if passphrase.is_some() {
+            warn_secret_on_command_line("--passphrase", "use --passphrase-env / --passphrase-fd");
+        }
+
+        let kdf = match matches.get_one::<String>("kdf").map(|s| s.as_str()) {
+            Some("pbkdf2") => Kdf::Pbkdf2Sha256 {
+                iterations: matches
+                    .get_one::<u32>("iterations")
+                    .copied()
+                    .unwrap_or(DEFAULT_PBKDF2_ITERATIONS),
+            },
+            _ => argon2_from_matches(matches),
+        };
 
-        // Appeler init_locker_with_passphrase avec ou sans passphrase
-        init_locker_with_passphrase(passphrase).expect("Failed to initialize the vault");
+        // Appeler init_locker_with_kdf avec ou sans passphrase
+        if !init_locker_with_kdf(passphrase, kdf).expect("Failed to initialize the vault") {
+            // Remplacement de la clé refusé : ne pas toucher à la configuration
+            return;
+        }
+        if let Some(cipher) = matches.get_one::<String>("cipher") {
+            let cipher = cipher.parse::<Cipher>().expect("Invalid cipher");
+            set_locker_cipher(cipher).expect("Failed to configure the cipher");
+        }
+        if let Some(keep_versions) = matches.get_one::<usize>("keep-versions") {
+            set_locker_keep_versions(*keep_versions).expect("Failed to configure the history");
+        }
 
         println!("{}", "✅ Vault initialized successfully!".green());
     } else if let Some(matches) = matches.subcommand_matches("encrypt") {
         let name = matches.get_one::<String>("name").expect("Name is required");
-        let value = if let Some(value) = matches.get_one::<String>("value") {
-            value.clone()
-        } else {
-            // Read from stdin if --value is not provided
-            let mut input = String::new();
-            match std::io::stdin().read_to_string(&mut input) {
-                Ok(_) => input.trim().to_string(),
-                Err(err) => {
-                    eprintln!("{}", format!("Error reading from stdin: {}", err).red());
-                    exit(1);
-                }
-            }
-        };
-
         let tags = matches
             .get_one::<String>("tags")
             .map(|t| t.split(',').map(|s| s.trim().to_string()).collect())
@@ -309,8 +928,66 @@ fn main() {
             })
             .unwrap_or(15);
 
+        // Un fichier est chiffré en flux, sauf s'il faut d'abord le nettoyer
+        let trim = matches.get_flag("trim");
+        let strip_newline = matches.get_flag("strip-newline");
+        let streamed_file = matches
+            .get_one::<String>("file")
+            .filter(|_| !trim && !strip_newline);
+
         // Encrypt the secret
-        match encrypt(&value, name, tags, Some(expiration)) {
+        let result = if let Some(path) = streamed_file {
+            encrypt_file(Path::new(path), name, tags, Some(expiration))
+        } else {
+            let value = if let Some(value) = matches.get_one::<String>("value") {
+                warn_secret_on_command_line("--value", "pipe the secret through stdin");
+                SecretBytes::from(value.as_bytes())
+            } else if let Some(path) = matches.get_one::<String>("file") {
+                // Lire le fichier tel quel, y compris les données binaires
+                match std::fs::read(path) {
+                    Ok(content) => SecretBytes::new(content),
+                    Err(err) => {
+                        eprintln!("{}", format!("Error reading '{}': {}", path, err).red());
+                        exit(1);
+                    }
+                }
+            } else if std::io::stdin().is_terminal() {
+                // Saisie interactive : masquée et confirmée
+                match prompt_new_secret("🔒 Secret value: ", "🔒 Confirm the secret value: ") {
+                    Ok(value) if value.is_empty() => {
+                        eprintln!("{}", "The secret value cannot be empty".red());
+                        exit(1);
+                    }
+                    Ok(value) => SecretBytes::from(value.expose().as_bytes()),
+                    Err(err) => {
+                        eprintln!("{}", format!("Error reading the secret: {}", err).red());
+                        exit(1);
+                    }
+                }
+            } else {
+                // Read the exact bytes from stdin if --value is not provided
+                let mut input = Zeroizing::new(Vec::new());
+                match std::io::stdin().read_to_end(&mut input) {
+                    Ok(_) => SecretBytes::from(input.as_slice()),
+                    Err(err) => {
+                        eprintln!("{}", format!("Error reading from stdin: {}", err).red());
+                        exit(1);
+                    }
+                }
+            };
+
+            // Le secret est conservé tel quel, sauf demande explicite
+            let value = if trim {
+                SecretBytes::from(trim_whitespace(value.expose()))
+            } else if strip_newline {
+                SecretBytes::from(strip_trailing_newline(value.expose()))
+            } else {
+                value
+            };
+
+            encrypt_bytes(&value, name, tags, Some(expiration))
+        };
+        match result {
             Ok(_) => println!(
                 "{}",
                 format!(
@@ -326,29 +1003,89 @@ fn main() {
         }
     } else if let Some(matches) = matches.subcommand_matches("decrypt") {
         let name = matches.get_one::<String>("name").expect("Name is required");
+        let version = matches.get_one::<u32>("version").copied();
 
-        match decrypt(name) {
-            Ok(decrypted_value) => {
-                if matches.get_flag("clipboard") {
-                    if let Err(err) = copy_to_clipboard(&decrypted_value) {
-                        eprintln!(
-                            "{}",
-                            format!("Error copying to the clipboard: {}", err).red()
-                        );
-                        exit(1);
-                    }
-                    println!("{}", "✅ Secret copied to the clipboard!".green());
-                } else {
-                    println!("{}", decrypted_value.green());
+        if let Some(path) = matches.get_one::<String>("output") {
+            // Déchiffré en flux, quelle que soit la taille du secret
+            let written = match version {
+                Some(version) => decrypt_version_to_file(name, version, Path::new(path)),
+                None => decrypt_to_file(name, Path::new(path)),
+            };
+            match written {
+                Ok(written) => println!(
+                    "{}",
+                    format!("✅ Secret written to '{}' ({} bytes).", path, written).green()
+                ),
+                Err(err) => {
+                    eprintln!("{}", format!("Error decrypting secret: {}", err).red());
+                    exit(1);
                 }
             }
+            return;
+        }
+
+        let decrypted = match version {
+            Some(version) => decrypt_version_bytes(name, version),
+            None => decrypt_bytes(name),
+        };
+        let (decrypted_value, value_type) = match decrypted {
+            Ok(decrypted) => decrypted,
             Err(err) => {
                 eprintln!("{}", format!("Error decrypting secret: {}", err).red());
                 exit(1);
             }
+        };
+
+        // Redirigé, le secret est écrit octet pour octet ; jamais de binaire dans un terminal
+        let is_binary = value_type == ValueType::Binary;
+        let to_terminal = std::io::stdout().is_terminal();
+        if !matches.get_flag("clipboard")
+            && (!to_terminal || (matches.get_flag("raw") && !is_binary))
+        {
+            let mut stdout = std::io::stdout();
+            if let Err(err) = stdout
+                .write_all(decrypted_value.expose())
+                .and_then(|_| stdout.flush())
+            {
+                eprintln!("{}", format!("Error writing secret: {}", err).red());
+                exit(1);
+            }
+            return;
+        }
+
+        let decrypted_value = match SecretString::from_utf8(decrypted_value) {
+            Ok(text) if !is_binary => text,
+            Ok(_) | Err(_) => {
+                eprintln!(
+                    "{}",
+                    format!(
+                        "⚠️ Secret '{}' holds binary data and is not printed to the terminal. Use --output <file> or pipe the output.",
+                        name
+                    )
+                    .yellow()
+                );
+                exit(1);
+            }
+        };
+
+        if matches.get_flag("clipboard") {
+            if let Err(err) = copy_to_clipboard(&decrypted_value) {
+                eprintln!(
+                    "{}",
+                    format!("Error copying to the clipboard: {}", err).red()
+                );
+                exit(1);
+            }
+            println!("{}", "✅ Secret copied to the clipboard!".green());
+        } else {
+            println!("{}", decrypted_value.expose().green());
         }
-    } else if matches.subcommand_matches("list").is_some() {
-        match list_secrets() {
+    } else if let Some(matches) = matches.subcommand_matches("list") {
+        let prefix = matches
+            .get_one::<String>("prefix")
+            .map(String::as_str)
+            .unwrap_or("");
+        match list_secrets(prefix) {
             Ok(secrets) => {
                 if secrets.is_empty() {
                     println!("{}", "⚠️ No secrets found.".yellow());
@@ -367,9 +1104,11 @@ fn main() {
     } else if let Some(matches) = matches.subcommand_matches("remove") {
         let name = matches.get_one::<String>("name");
         let remove_all = matches.get_flag("all"); // Vérifie si --all est présent
+        let force = matches.get_flag("force");
 
-        if let Err(e) = remove_secret(name.map(|s| s.as_str()), remove_all) {
-            eprintln!("Error: {}", e);
+        if let Err(e) = remove_secret(name.map(|s| s.as_str()), remove_all, force) {
+            eprintln!("{}", format!("Error: {}", e).red());
+            exit(1);
         } else if remove_all {
             println!("{}", "✅ All secrets deleted successfully!".green());
         } else if let Some(secret_name) = name {
@@ -378,6 +1117,141 @@ fn main() {
                 format!("✅ Secret '{}' deleted successfully!", secret_name).green()
             );
         }
+    } else if let Some(matches) = matches.subcommand_matches("restore") {
+        if let Some(input) = matches.get_one::<String>("input") {
+            let on_conflict = matches
+                .get_one::<String>("on-conflict")
+                .unwrap()
+                .parse::<ConflictPolicy>()
+                .expect("Invalid conflict policy");
+            let backup_passphrase_env = matches
+                .get_one::<String>("backup-passphrase-env")
+                .map(|var| var.as_str());
+            match restore_vault(Path::new(input), on_conflict, backup_passphrase_env) {
+                Ok(report) => print_restore_report(&report, input),
+                Err(SmartLockerError::RestoreInterrupted { report, error }) => {
+                    // Les secrets déjà importés restent dans le vault : les lister
+                    print_restore_report(&report, input);
+                    eprintln!("{}", format!("Error restoring the backup: {}", error).red());
+                    exit(1);
+                }
+                Err(err) => {
+                    eprintln!("{}", format!("Error restoring the backup: {}", err).red());
+                    exit(1);
+                }
+            }
+            return;
+        }
+        let name = matches.get_one::<String>("name").unwrap();
+        match restore_secret(name) {
+            Ok(_) => println!(
+                "{}",
+                format!("✅ Secret '{}' restored from the trash!", name).green()
+            ),
+            Err(err) => {
+                eprintln!("{}", format!("Error restoring secret: {}", err).red());
+                exit(1);
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("rename") {
+        let name = matches.get_one::<String>("name").unwrap();
+        let to = matches.get_one::<String>("to").unwrap();
+        match rename_secret(name, to, matches.get_flag("force")) {
+            Ok(_) => println!(
+                "{}",
+                format!("✅ Secret '{}' renamed to '{}'!", name, to).green()
+            ),
+            Err(err) => {
+                eprintln!("{}", format!("Error renaming secret: {}", err).red());
+                exit(1);
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("copy") {
+        let name = matches.get_one::<String>("name").unwrap();
+        let to = matches.get_one::<String>("to").unwrap();
+        match copy_secret(name, to, matches.get_flag("force")) {
+            Ok(_) => println!(
+                "{}",
+                format!("✅ Secret '{}' copied to '{}'!", name, to).green()
+            ),
+            Err(err) => {
+                eprintln!("{}", format!("Error copying secret: {}", err).red());
+                exit(1);
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("move") {
+        let name = matches.get_one::<String>("name").unwrap();
+        let to_vault = matches.get_one::<String>("to-vault").unwrap();
+        match move_secret(name, to_vault, matches.get_flag("force")) {
+            Ok(_) => println!(
+                "{}",
+                format!("✅ Secret '{}' moved to the vault '{}'!", name, to_vault).green()
+            ),
+            Err(err) => {
+                eprintln!("{}", format!("Error moving secret: {}", err).red());
+                exit(1);
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("trash") {
+        let result = match matches.subcommand() {
+            Some(("list", _)) => list_trash().map(|secrets| {
+                if secrets.is_empty() {
+                    println!("{}", "🗑️ The trash is empty.".yellow());
+                } else {
+                    println!("{}", "🗑️ Removed secrets:".blue());
+                    for secret in secrets {
+                        println!("{}", secret);
+                    }
+                }
+            }),
+            Some(("purge", matches)) => matches
+                .get_one::<String>("older-than")
+                .map(|duration| parse_duration(duration))
+                .transpose()
+                .and_then(purge_trash)
+                .map(|count| {
+                    println!(
+                        "{}",
+                        format!("✅ {} secret(s) deleted for good.", count).green()
+                    )
+                }),
+            _ => unreachable!("a trash subcommand is required"),
+        };
+        if let Err(err) = result {
+            eprintln!("{}", format!("Error managing the trash: {}", err).red());
+            exit(1);
+        }
+    } else if let Some(matches) = matches.subcommand_matches("history") {
+        let name = matches.get_one::<String>("name").unwrap();
+        match secret_history(name) {
+            Ok(versions) => {
+                println!("{}", format!("🕘 Versions of '{}':", name).blue());
+                for version in versions {
+                    println!("{}", version);
+                }
+            }
+            Err(err) => {
+                eprintln!("{}", format!("Error reading the history: {}", err).red());
+                exit(1);
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("rollback") {
+        let name = matches.get_one::<String>("name").unwrap();
+        let to_version = *matches.get_one::<u32>("to").unwrap();
+        match rollback_secret(name, to_version) {
+            Ok(version) => println!(
+                "{}",
+                format!(
+                    "✅ Secret '{}' rolled back to version {} (now version {}).",
+                    name, to_version, version
+                )
+                .green()
+            ),
+            Err(err) => {
+                eprintln!("{}", format!("Error rolling back secret: {}", err).red());
+                exit(1);
+            }
+        }
     } else if let Some(matches) = matches.subcommand_matches("renew") {
         let name = matches.get_one::<String>("name").unwrap();
         let days: u64 = matches
@@ -393,9 +1267,64 @@ fn main() {
                 exit(1);
             }
         }
+    } else if let Some(matches) = matches.subcommand_matches("doctor") {
+        match doctor(matches.get_flag("fix")) {
+            Ok(code) => exit(code),
+            Err(err) => {
+                eprintln!("{}", format!("Error checking the vault: {}", err).red());
+                exit(EXIT_FAILED);
+            }
+        }
+    } else if matches.subcommand_matches("passwd").is_some() {
+        let new_passphrase = read_new_passphrase("🔑 New passphrase: ").unwrap_or_else(|err| {
+            eprintln!("{}", format!("Error reading passphrase: {}", err).red());
+            exit(1);
+        });
+        if let Err(err) = change_passphrase(new_passphrase.expose()) {
+            eprintln!(
+                "{}",
+                format!("Error changing the passphrase: {}", err).red()
+            );
+            exit(1);
+        }
+    } else if matches.subcommand_matches("rotate-key").is_some() {
+        if let Err(err) = rotate_key() {
+            eprintln!("{}", format!("Error rotating the key: {}", err).red());
+            exit(1);
+        }
+    } else if let Some(matches) = matches.subcommand_matches("upgrade-kdf") {
+        let passphrase = match matches.get_one::<String>("passphrase") {
+            Some(passphrase) => {
+                warn_secret_on_command_line(
+                    "--passphrase",
+                    "use --passphrase-env / --passphrase-fd",
+                );
+                SecretString::from(passphrase.as_str())
+            }
+            None => read_passphrase("🔑 Passphrase: ").unwrap_or_else(|err| {
+                eprintln!("{}", format!("Error reading passphrase: {}", err).red());
+                exit(1);
+            }),
+        };
+        if let Err(err) = upgrade_kdf(passphrase.expose(), argon2_from_matches(matches)) {
+            eprintln!("{}", format!("Error upgrading the key: {}", err).red());
+            exit(1);
+        }
     } else if matches.subcommand_matches("backup-key").is_some() {
         backup_key().expect("Failed to create a backup of the encryption key");
         println!("{}", "✅ Encryption key backed up successfully!".green());
+    } else if let Some(matches) = matches.subcommand_matches("backup") {
+        let output = matches.get_one::<String>("output").unwrap();
+        match backup_vault(Path::new(output)) {
+            Ok(count) => println!(
+                "{}",
+                format!("✅ {} secret(s) backed up to {}.", count, output).green()
+            ),
+            Err(err) => {
+                eprintln!("{}", format!("Error backing up the vault: {}", err).red());
+                exit(1);
+            }
+        }
     } else if matches.subcommand_matches("restore-key").is_some() {
         restore_key().expect("Failed to restore the encryption key");
         println!("{}", "✅ Encryption key restored successfully!".green());
@@ -403,9 +1332,116 @@ fn main() {
         let format = matches.get_one::<String>("format").unwrap();
         let output = matches.get_one::<String>("output").map(|s| s.as_str());
         export(format, output).expect("Failed to export secrets");
+    } else if let Some(matches) = matches.subcommand_matches("vault") {
+        let result = match matches.subcommand() {
+            Some(("create", matches)) => create_vault(
+                matches.get_one::<String>("name").unwrap(),
+                matches.get_one::<String>("path").map(Path::new),
+            ),
+            Some(("list", _)) => list_vaults().map(|vaults| {
+                println!("{}", "🗄️ Vaults (* = default):".blue());
+                for vault in vaults {
+                    println!("{}", vault);
+                }
+            }),
+            Some(("current", _)) => {
+                current_vault().map(|vault| println!("🗄️ Vault in use: {}", vault))
+            }
+            Some(("remove", matches)) => remove_vault(matches.get_one::<String>("name").unwrap()),
+            Some(("default", matches)) => {
+                set_default_vault(matches.get_one::<String>("name").unwrap())
+            }
+            _ => unreachable!("a vault subcommand is required"),
+        };
+        if let Err(err) = result {
+            eprintln!("{}", format!("Error managing vaults: {}", err).red());
+            exit(1);
+        }
+    }
+}
+
+/// Arguments shared by `rename`, `copy` and `move`.
+fn transfer_args(name_help: &'static str) -> [Arg; 2] {
+    [
+        Arg::new("name")
+            .short('n')
+            .long("name")
+            .num_args(1)
+            .required(true)
+            .help(name_help),
+        Arg::new("force")
+            .long("force")
+            .action(clap::ArgAction::SetTrue)
+            .help("Replaces an existing secret with the same name, moving it to the trash"),
+    ]
+}
+
+/// Arguments tuning the Argon2id cost, shared by `init` and `upgrade-kdf`.
+fn argon2_args() -> [Arg; 2] {
+    [
+        Arg::new("memory")
+            .long("memory")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u32))
+            .help("Argon2id memory cost in KiB (default: 19456, at most 1048576)"),
+        Arg::new("parallelism")
+            .long("parallelism")
+            .num_args(1)
+            .value_parser(clap::value_parser!(u32))
+            .help("Argon2id parallelism (default: 1, at most 64)"),
+    ]
+}
+
+/// Builds the Argon2id parameters from the command line, falling back to the defaults.
+fn argon2_from_matches(matches: &clap::ArgMatches) -> Kdf {
+    Kdf::Argon2id {
+        memory_kib: matches
+            .get_one::<u32>("memory")
+            .copied()
+            .unwrap_or(DEFAULT_ARGON2_MEMORY_KIB),
+        iterations: matches
+            .get_one::<u32>("iterations")
+            .copied()
+            .unwrap_or(DEFAULT_ARGON2_ITERATIONS),
+        parallelism: matches
+            .get_one::<u32>("parallelism")
+            .copied()
+            .unwrap_or(DEFAULT_ARGON2_PARALLELISM),
+    }
+}
+
+/// Prints the secrets restored from a backup, with those replaced and skipped.
+fn print_restore_report(report: &RestoreReport, input: &str) {
+    println!(
+        "{}",
+        format!(
+            "✅ {} secret(s) restored from {}.",
+            report.restored.len() + report.replaced.len(),
+            input
+        )
+        .green()
+    );
+    for name in &report.replaced {
+        println!("  - '{}' replaced, the previous one is in the trash", name);
+    }
+    for name in &report.skipped {
+        println!("  - '{}' skipped, it already exists", name);
     }
 }
 
+/// Warns that a secret passed as an argument is visible in the shell history and the process list.
+fn warn_secret_on_command_line(flag: &str, alternative: &str) {
+    eprintln!(
+        "{}",
+        format!(
+            "⚠️ {} exposes the secret in your shell history and the process list. \
+            Omit it to be prompted, or {}.",
+            flag, alternative
+        )
+        .yellow()
+    );
+}
+
 fn display_logo() {
     println!(
         "{}",
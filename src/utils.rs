@@ -1,3 +1,14 @@
 pub mod config;
+pub mod crypto;
+pub mod format;
+pub mod interaction;
+pub mod kdf;
+pub mod keyfile;
+pub mod lock;
 pub mod metadata;
+pub mod name;
+pub mod passphrase;
+pub mod secret;
+pub mod stream;
 pub mod toolbox;
+pub mod vault;
@@ -1,16 +1,249 @@
+use crate::utils::secret::SecretBytes;
+use crate::utils::toolbox::write_atomic;
+use crate::LockerResult;
+use crate::SmartLockerError;
 use aes_gcm::aead::consts::U12;
-use aes_gcm::aead::KeyInit;
+use aes_gcm::aead::generic_array::typenum::Unsigned;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
 use aes_gcm::{Aes256Gcm, Key, Nonce};
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
 use flate2::write::GzEncoder;
 use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
 
 pub const SIGNATURE: &[u8; 8] = b"SMARTLKR"; // Signature fixe pour identifier le format
-pub const FORMAT_VERSION: u8 = 1; // Version actuelle du format
+pub const FORMAT_VERSION: u8 = 4; // Version actuelle du format (chiffrement par blocs)
+pub const SINGLE_SHOT_FORMAT_VERSION: u8 = 3; // Algorithme dans l'en-tête, un seul appel AEAD
+pub const AES_ONLY_FORMAT_VERSION: u8 = 2; // En-tête et nom authentifiés, AES-GCM uniquement
+pub const LEGACY_FORMAT_VERSION: u8 = 1; // Ancien format, toujours lisible
 pub const NONCE_SIZE: usize = 12; // Taille du nonce (12 octets pour AES-GCM)
+pub const TAG_SIZE: usize = 16; // Taille du tag d'authentification (identique pour tous les algorithmes)
 pub const KEY_SIZE: usize = 32; // Taille de la clé symétrique (32 octets pour AES-256)
+pub const STREAM_CHUNK_SIZE: usize = 64 * 1024; // Taille des blocs chiffrés à partir du format 4
+pub const STREAM_NONCE_SUFFIX_SIZE: usize = 5; // Compteur de bloc (4 octets) et drapeau de dernier bloc
+pub const LOCKER_CONFIG_FILE: &str = "config.json"; // Configuration propre à chaque locker
+pub const DEFAULT_KEEP_VERSIONS: usize = 5; // Versions précédentes gardées pour chaque secret
+
+/// AEAD cipher used to encrypt a secret, recorded in the `.slock` header.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cipher {
+    /// AES-256-GCM with a 12-byte nonce, the historical cipher.
+    #[default]
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+    /// ChaCha20-Poly1305 with a 12-byte nonce, fast without AES-NI.
+    #[serde(rename = "chacha20-poly1305")]
+    ChaCha20Poly1305,
+    /// XChaCha20-Poly1305 with a 24-byte nonce, safe to randomize for very large vaults.
+    #[serde(rename = "xchacha20-poly1305")]
+    XChaCha20Poly1305,
+}
+
+impl Cipher {
+    /// Every supported cipher, in identifier order.
+    pub const ALL: [Cipher; 3] = [
+        Cipher::Aes256Gcm,
+        Cipher::ChaCha20Poly1305,
+        Cipher::XChaCha20Poly1305,
+    ];
+
+    /// Returns the identifier stored in the `.slock` header.
+    pub fn id(&self) -> u8 {
+        match self {
+            Cipher::Aes256Gcm => 1,
+            Cipher::ChaCha20Poly1305 => 2,
+            Cipher::XChaCha20Poly1305 => 3,
+        }
+    }
+
+    /// Returns the cipher for a header identifier, if it is known.
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|cipher| cipher.id() == id)
+    }
+
+    /// Returns the name used on the command line and in `config.json`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Cipher::Aes256Gcm => "aes-256-gcm",
+            Cipher::ChaCha20Poly1305 => "chacha20-poly1305",
+            Cipher::XChaCha20Poly1305 => "xchacha20-poly1305",
+        }
+    }
+
+    /// Returns the size of the nonce in bytes.
+    pub fn nonce_size(&self) -> usize {
+        match self {
+            Cipher::Aes256Gcm | Cipher::ChaCha20Poly1305 => NONCE_SIZE,
+            Cipher::XChaCha20Poly1305 => 24,
+        }
+    }
+
+    /// Returns the size of the random nonce prefix of a chunked (version 4) payload.
+    pub fn nonce_prefix_size(&self) -> usize {
+        self.nonce_size() - STREAM_NONCE_SUFFIX_SIZE
+    }
+
+    /// Generates a random nonce prefix for a chunked payload.
+    pub fn generate_nonce_prefix(&self) -> Vec<u8> {
+        let mut prefix = vec![0u8; self.nonce_prefix_size()];
+        rand::fill(&mut prefix[..]);
+        prefix
+    }
+
+    /// Generates a random nonce of the right size for this cipher.
+    pub fn generate_nonce(&self) -> Vec<u8> {
+        let mut nonce = vec![0u8; self.nonce_size()];
+        rand::fill(&mut nonce[..]);
+        nonce
+    }
+
+    /// Encrypts a payload with the given key and nonce.
+    pub fn encrypt(
+        &self,
+        key_data: &[u8],
+        nonce: &[u8],
+        payload: Payload,
+    ) -> Result<Vec<u8>, String> {
+        match self {
+            Cipher::Aes256Gcm => seal_with::<Aes256Gcm>(key_data, nonce, payload),
+            Cipher::ChaCha20Poly1305 => seal_with::<ChaCha20Poly1305>(key_data, nonce, payload),
+            Cipher::XChaCha20Poly1305 => seal_with::<XChaCha20Poly1305>(key_data, nonce, payload),
+        }
+    }
+
+    /// Decrypts and authenticates a payload with the given key and nonce.
+    pub fn decrypt(
+        &self,
+        key_data: &[u8],
+        nonce: &[u8],
+        payload: Payload,
+    ) -> Result<Vec<u8>, String> {
+        match self {
+            Cipher::Aes256Gcm => open_with::<Aes256Gcm>(key_data, nonce, payload),
+            Cipher::ChaCha20Poly1305 => open_with::<ChaCha20Poly1305>(key_data, nonce, payload),
+            Cipher::XChaCha20Poly1305 => open_with::<XChaCha20Poly1305>(key_data, nonce, payload),
+        }
+    }
+}
+
+impl fmt::Display for Cipher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Cipher {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|cipher| cipher.name() == name)
+            .ok_or_else(|| format!("Unknown cipher '{}'", name))
+    }
+}
+
+fn init_aead<C: KeyInit>(key_data: &[u8], nonce: &[u8], nonce_size: usize) -> Result<C, String> {
+    if key_data.len() != KEY_SIZE {
+        return Err(format!(
+            "Invalid key size: expected {} bytes, got {} bytes",
+            KEY_SIZE,
+            key_data.len()
+        ));
+    }
+    if nonce.len() != nonce_size {
+        return Err(format!(
+            "Invalid nonce size: expected {} bytes, got {} bytes",
+            nonce_size,
+            nonce.len()
+        ));
+    }
+    C::new_from_slice(key_data).map_err(|e| e.to_string())
+}
+
+fn seal_with<C: KeyInit + Aead>(
+    key_data: &[u8],
+    nonce: &[u8],
+    payload: Payload,
+) -> Result<Vec<u8>, String> {
+    let cipher = init_aead::<C>(key_data, nonce, C::NonceSize::USIZE)?;
+    cipher
+        .encrypt(aes_gcm::aead::Nonce::<C>::from_slice(nonce), payload)
+        .map_err(|e| format!("Error during encryption: {}", e))
+}
+
+fn open_with<C: KeyInit + Aead>(
+    key_data: &[u8],
+    nonce: &[u8],
+    payload: Payload,
+) -> Result<Vec<u8>, String> {
+    let cipher = init_aead::<C>(key_data, nonce, C::NonceSize::USIZE)?;
+    cipher
+        .decrypt(aes_gcm::aead::Nonce::<C>::from_slice(nonce), payload)
+        .map_err(|_| "Decryption failed".to_string())
+}
+
+/// Per-locker settings, stored in `config.json` next to the key.
+///
+/// A missing file means the defaults, so lockers created before it existed
+/// keep working unchanged.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LockerConfig {
+    /// Cipher used for newly encrypted secrets.
+    #[serde(default)]
+    pub cipher: Cipher,
+    /// Number of previous versions kept for each secret; older ones are pruned
+    /// the next time the secret is written. `0` disables the history.
+    #[serde(default = "default_keep_versions")]
+    pub keep_versions: usize,
+}
+
+fn default_keep_versions() -> usize {
+    DEFAULT_KEEP_VERSIONS
+}
+
+impl Default for LockerConfig {
+    fn default() -> Self {
+        Self {
+            cipher: Cipher::default(),
+            keep_versions: DEFAULT_KEEP_VERSIONS,
+        }
+    }
+}
+
+impl LockerConfig {
+    /// Reads the configuration of a locker, falling back to the defaults.
+    pub fn load(locker_dir: &Path) -> LockerResult<Self> {
+        let config_path = locker_dir.join(LOCKER_CONFIG_FILE);
+        if !config_path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read(&config_path).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Unable to read {:?}: {}", config_path, e))
+        })?;
+        serde_json::from_slice(&content).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error parsing {:?}: {}", config_path, e))
+        })
+    }
+
+    /// Writes the configuration of a locker atomically.
+    pub fn save(&self, locker_dir: &Path) -> LockerResult<()> {
+        let content = serde_json::to_string_pretty(self).map_err(|e| {
+            SmartLockerError::FileSystemError(format!(
+                "Error serializing the locker configuration: {}",
+                e
+            ))
+        })?;
+        write_atomic(&locker_dir.join(LOCKER_CONFIG_FILE), content.as_bytes())
+    }
+}
 
 /// Configuration structure for encryption settings.
-/// This structure defines the parameters used for AES-GCM encryption,
+/// This structure defines the parameters used for AES-GCM encryption of the key file,
 /// including the signature, format version, nonce size, key size, and compression settings.
 pub struct EncryptionConfig {
     /// Fixed signature to identify the format.
@@ -46,12 +279,13 @@ impl EncryptionConfig {
     /// Initializes the AES-GCM cipher with the provided key.
     ///
     /// # Arguments
-    /// * `key_data` - A byte slice containing the encryption key.
+    /// * `key_data` - The encryption key.
     ///
     /// # Returns
     /// * `Ok(Aes256Gcm)` - The initialized AES-GCM cipher.
     /// * `Err(String)` - An error message if the key size is invalid.
-    pub fn init_cipher(&self, key_data: &[u8]) -> Result<Aes256Gcm, String> {
+    pub fn init_cipher(&self, key_data: &SecretBytes) -> Result<Aes256Gcm, String> {
+        let key_data = key_data.expose();
         if key_data.len() != self.key_size {
             return Err(format!(
                 "Invalid key size: expected {} bytes, got {} bytes",
@@ -0,0 +1,243 @@
+use crate::utils::config::{Cipher, EncryptionConfig, FORMAT_VERSION, LEGACY_FORMAT_VERSION};
+use crate::utils::format::{parse_slock, parse_slock_header, SlockHeader, MAX_HEADER_SIZE};
+use crate::utils::secret::SecretBytes;
+use crate::utils::stream::{copy_secret, StreamDecryptor, StreamEncryptor};
+use crate::LockerResult;
+use crate::SmartLockerError;
+use aes_gcm::aead::Payload;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use std::io::{self, Cursor, Read, Write};
+use zeroize::Zeroizing;
+
+/// Compresses and encrypts a secret value with the given key and cipher.
+///
+/// The header (signature, version, cipher, nonce prefix length and nonce prefix)
+/// and the secret name are authenticated as associated data, so the file cannot
+/// be renamed or its header altered without decryption failing.
+///
+/// # Arguments
+/// * `name` - The canonical name of the secret.
+/// * `plaintext` - The raw bytes of the secret.
+/// * `key_data` - The symmetric key of the locker.
+/// * `cipher` - The cipher to encrypt with, recorded in the header.
+///
+/// # Returns
+/// * `Ok(Vec<u8>)` - The content of the `.slock` file (header and ciphertext).
+/// * `Err(SmartLockerError)` - If compression or encryption fails.
+pub fn seal_secret(
+    name: &str,
+    plaintext: &[u8],
+    key_data: &SecretBytes,
+    cipher: Cipher,
+) -> LockerResult<Vec<u8>> {
+    seal_secret_stream(name, &mut &plaintext[..], Vec::new(), key_data, cipher)
+}
+
+/// Compresses and encrypts a secret read from `reader`, writing the `.slock` content to `writer`.
+///
+/// The value is processed in chunks, so secrets of any size are encrypted in constant memory.
+///
+/// # Returns
+/// * `Ok(W)` - The writer, once the final chunk is written.
+/// * `Err(SmartLockerError)` - If reading, compression or encryption fails.
+pub fn seal_secret_stream<R: Read, W: Write>(
+    name: &str,
+    reader: &mut R,
+    mut writer: W,
+    key_data: &SecretBytes,
+    cipher: Cipher,
+) -> LockerResult<W> {
+    let config = EncryptionConfig::new();
+
+    // Générer un préfixe de nonce aléatoire, complété par le compteur de chaque bloc
+    let nonce_prefix = cipher.generate_nonce_prefix();
+
+    // Construire l'en-tête versionné
+    let mut header = Vec::new();
+    header.extend_from_slice(config.signature); // Ajouter la signature
+    header.push(FORMAT_VERSION); // Ajouter la version
+    header.push(cipher.id()); // Ajouter l'algorithme
+    header.push(nonce_prefix.len() as u8); // Ajouter la taille du préfixe de nonce
+    header.extend_from_slice(&nonce_prefix); // Ajouter le préfixe de nonce
+    let write_error = |e: io::Error| {
+        SmartLockerError::EncryptionError(format!("Error when writing encrypted data: {}", e))
+    };
+    writer.write_all(&header).map_err(write_error)?;
+
+    // Compresser puis chiffrer bloc par bloc en authentifiant l'en-tête et le nom
+    let aad = associated_data(&header, name);
+    let encryptor = StreamEncryptor::new(writer, cipher, key_data, nonce_prefix, aad);
+    let mut encoder = GzEncoder::new(encryptor, config.compression);
+    copy_secret(reader, &mut encoder).map_err(|e| {
+        SmartLockerError::EncryptionError(format!("Error during data compression: {}", e))
+    })?;
+    let encryptor = encoder.finish().map_err(|e| {
+        SmartLockerError::EncryptionError(format!("Error when finalizing compression: {}", e))
+    })?;
+    encryptor.finish().map_err(write_error)
+}
+
+/// Decrypts and decompresses the content of a `.slock` file with the given key.
+///
+/// The cipher is read from the header, so a locker can hold secrets written
+/// under different ciphers. Version 1 files (no associated data) are still accepted.
+///
+/// # Arguments
+/// * `name` - The canonical name of the secret, authenticated from version 2.
+/// * `encrypted_data` - The content of the `.slock` file.
+/// * `key_data` - The symmetric key of the locker.
+///
+/// # Returns
+/// * `Ok(SecretBytes)` - The raw bytes of the secret, wiped from memory when dropped.
+/// * `Err(SmartLockerError)` - `FormatError` if the file is malformed, `DecryptionError`
+///   if it does not authenticate.
+pub fn open_secret(
+    name: &str,
+    encrypted_data: &[u8],
+    key_data: &SecretBytes,
+) -> LockerResult<SecretBytes> {
+    // Analyser l'en-tête sans jamais paniquer
+    let slock = parse_slock(encrypted_data)?;
+
+    let mut decompressed_data = Zeroizing::new(Vec::new());
+    if slock.header.version == FORMAT_VERSION {
+        let mut decoder = GzDecoder::new(stream_decryptor(
+            name,
+            &slock.header,
+            slock.ciphertext,
+            key_data,
+        ));
+        copy_secret(&mut decoder, &mut *decompressed_data).map_err(stream_error)?;
+        return Ok(SecretBytes::new(std::mem::take(&mut *decompressed_data)));
+    }
+
+    // Déchiffrer les données (le format 1 n'authentifie ni l'en-tête ni le nom)
+    let aad = match slock.header.version {
+        LEGACY_FORMAT_VERSION => Vec::new(),
+        _ => associated_data(slock.header.raw, name),
+    };
+    let decrypted_data = Zeroizing::new(
+        slock
+            .header
+            .cipher
+            .decrypt(
+                key_data.expose(),
+                slock.header.nonce,
+                Payload {
+                    msg: slock.ciphertext,
+                    aad: &aad,
+                },
+            )
+            .map_err(SmartLockerError::DecryptionError)?,
+    );
+
+    // Décompresser les données
+    let mut decoder = GzDecoder::new(&decrypted_data[..]);
+    decoder.read_to_end(&mut decompressed_data).map_err(|_| {
+        SmartLockerError::FileSystemError("Failed to decompress the data".to_string())
+    })?;
+    Ok(SecretBytes::new(std::mem::take(&mut *decompressed_data)))
+}
+
+/// Decrypts a `.slock` file read from `reader`, writing the secret to `writer`.
+///
+/// Version 4 files are decrypted chunk by chunk in constant memory, and only
+/// authenticated chunks reach the writer. Older files are read in memory.
+///
+/// # Returns
+/// * `Ok(u64)` - The number of bytes written.
+/// * `Err(SmartLockerError)` - `FormatError` if the file is malformed, `DecryptionError`
+///   if a chunk does not authenticate.
+pub fn open_secret_stream<R: Read, W: Write>(
+    name: &str,
+    reader: &mut R,
+    writer: &mut W,
+    key_data: &SecretBytes,
+) -> LockerResult<u64> {
+    let read_error = |e: io::Error| {
+        SmartLockerError::FileSystemError(format!("Unable to read the encrypted file: {}", e))
+    };
+
+    // Lire juste assez d'octets pour analyser l'en-tête
+    let mut start = Vec::with_capacity(MAX_HEADER_SIZE);
+    reader
+        .take(MAX_HEADER_SIZE as u64)
+        .read_to_end(&mut start)
+        .map_err(read_error)?;
+
+    let header = parse_slock_header(&start)?;
+    if header.version != FORMAT_VERSION {
+        // Les anciens formats sont déchiffrés en un seul appel
+        reader.read_to_end(&mut start).map_err(read_error)?;
+        let decrypted_data = open_secret(name, &start, key_data)?;
+        writer
+            .write_all(decrypted_data.expose())
+            .map_err(stream_error)?;
+        return Ok(decrypted_data.len() as u64);
+    }
+
+    let ciphertext = Cursor::new(&start[header.raw.len()..]).chain(reader);
+    let mut decoder = GzDecoder::new(stream_decryptor(name, &header, ciphertext, key_data));
+    copy_secret(&mut decoder, writer).map_err(stream_error)
+}
+
+/// Finds which of the candidate names a `.slock` file was encrypted for.
+///
+/// Used to tell a moved or renamed file apart from a corrupted one: a file
+/// that authenticates under another secret's name was swapped on disk.
+/// For version 4 files, the beginning of the file is enough: the first chunk and one more byte.
+pub fn find_bound_name<'a>(
+    encrypted_data: &[u8],
+    key_data: &SecretBytes,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    candidates
+        .into_iter()
+        .find(|candidate| authenticates(candidate, encrypted_data, key_data))
+}
+
+/// Checks that a `.slock` file was encrypted for `name`.
+///
+/// For version 4 files only the first chunk is decrypted, so `encrypted_data`
+/// may be the beginning of the file as long as it goes one byte past that chunk.
+fn authenticates(name: &str, encrypted_data: &[u8], key_data: &SecretBytes) -> bool {
+    match parse_slock(encrypted_data) {
+        Ok(slock) if slock.header.version == FORMAT_VERSION => {
+            stream_decryptor(name, &slock.header, slock.ciphertext, key_data)
+                .read(&mut [0u8; 1])
+                .is_ok()
+        }
+        Ok(_) => open_secret(name, encrypted_data, key_data).is_ok(),
+        Err(_) => false,
+    }
+}
+
+fn stream_decryptor<'a, R: Read>(
+    name: &str,
+    header: &SlockHeader,
+    ciphertext: R,
+    key_data: &'a SecretBytes,
+) -> StreamDecryptor<'a, R> {
+    StreamDecryptor::new(
+        ciphertext,
+        header.cipher,
+        key_data,
+        header.nonce.to_vec(),
+        associated_data(header.raw, name),
+    )
+}
+
+// Un bloc non authentifié est une erreur de déchiffrement, le reste une erreur d'entrée/sortie
+fn stream_error(e: io::Error) -> SmartLockerError {
+    match e.kind() {
+        io::ErrorKind::InvalidData => SmartLockerError::DecryptionError(e.to_string()),
+        _ => SmartLockerError::FileSystemError(format!("Error during decryption: {}", e)),
+    }
+}
+
+fn associated_data(header: &[u8], name: &str) -> Vec<u8> {
+    let mut aad = header.to_vec();
+    aad.extend_from_slice(name.as_bytes());
+    aad
+}
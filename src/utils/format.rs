@@ -0,0 +1,156 @@
+use crate::utils::config::{
+    Cipher, AES_ONLY_FORMAT_VERSION, FORMAT_VERSION, LEGACY_FORMAT_VERSION, SIGNATURE,
+    SINGLE_SHOT_FORMAT_VERSION, TAG_SIZE,
+};
+use thiserror::Error;
+
+/// Upper bound on the size of a `.slock` header, whatever its version and cipher.
+pub const MAX_HEADER_SIZE: usize = 64;
+
+/// Errors reported while parsing a `.slock` file.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum FormatError {
+    /// The file ends before the header or the authentication tag.
+    #[error("truncated file: expected at least {expected} bytes, got {actual}")]
+    Truncated { expected: usize, actual: usize },
+    /// The file does not start with the smart-locker signature.
+    #[error("bad signature: this is not a smart-locker secret file")]
+    BadSignature,
+    /// The format version is not supported by this version of smart-locker.
+    #[error("unknown format version {0}, please update smart-locker")]
+    UnknownVersion(u8),
+    /// The cipher identifier is not supported by this version of smart-locker.
+    #[error("unknown cipher {0}, please update smart-locker")]
+    UnknownCipher(u8),
+    /// The nonce length recorded in the header does not match the cipher.
+    #[error("bad nonce length: expected {expected} bytes, got {actual}")]
+    BadNonceLength { expected: usize, actual: usize },
+}
+
+/// The header of a `.slock` file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlockHeader<'a> {
+    /// Format version.
+    pub version: u8,
+    /// Cipher the payload is encrypted with.
+    pub cipher: Cipher,
+    /// Nonce used to encrypt the payload (the nonce prefix of every chunk from version 4).
+    pub nonce: &'a [u8],
+    /// Raw header bytes, authenticated as associated data from version 2.
+    pub raw: &'a [u8],
+}
+
+/// A parsed `.slock` file: typed header plus ciphertext.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlockFile<'a> {
+    pub header: SlockHeader<'a>,
+    /// Encrypted payload, including the authentication tag.
+    pub ciphertext: &'a [u8],
+}
+
+/// Parses the content of a `.slock` file without ever panicking.
+///
+/// Layouts:
+/// * version 1: `SIGNATURE | 1 | nonce (12) | ciphertext`
+/// * version 2: `SIGNATURE | 2 | nonce length | nonce | ciphertext` (AES-256-GCM)
+/// * version 3: `SIGNATURE | 3 | cipher id | nonce length | nonce | ciphertext`
+/// * version 4: `SIGNATURE | 4 | cipher id | nonce prefix length | nonce prefix | chunks`,
+///   each chunk sealed separately (see [`crate::utils::stream`])
+///
+/// # Arguments
+/// * `data` - The raw content of the file.
+///
+/// # Returns
+/// * `Ok(SlockFile)` - The header and ciphertext, borrowed from `data`.
+/// * `Err(FormatError)` - What is wrong with the file.
+pub fn parse_slock(data: &[u8]) -> Result<SlockFile<'_>, FormatError> {
+    let header = parse_slock_header(data)?;
+    let header_len = header.raw.len();
+
+    // Le chiffré contient au moins le tag d'authentification
+    let ciphertext = &data[header_len..];
+    if ciphertext.len() < TAG_SIZE {
+        return Err(FormatError::Truncated {
+            expected: header_len + TAG_SIZE,
+            actual: data.len(),
+        });
+    }
+
+    Ok(SlockFile { header, ciphertext })
+}
+
+/// Parses only the header of a `.slock` file.
+///
+/// `data` may be the beginning of the file: reading [`MAX_HEADER_SIZE`] bytes
+/// is enough to parse any header, so large files can be decrypted as a stream.
+pub fn parse_slock_header(data: &[u8]) -> Result<SlockHeader<'_>, FormatError> {
+    let mut reader = Reader::new(data);
+
+    // Une signature incomplète est une troncature, une signature différente un autre format
+    let signature_len = SIGNATURE.len().min(data.len());
+    if data[..signature_len] != SIGNATURE[..signature_len] {
+        return Err(FormatError::BadSignature);
+    }
+    reader.take(SIGNATURE.len())?;
+
+    let version = reader.take(1)?[0];
+    let cipher = match version {
+        LEGACY_FORMAT_VERSION | AES_ONLY_FORMAT_VERSION => Cipher::Aes256Gcm,
+        SINGLE_SHOT_FORMAT_VERSION | FORMAT_VERSION => {
+            let cipher_id = reader.take(1)?[0];
+            Cipher::from_id(cipher_id).ok_or(FormatError::UnknownCipher(cipher_id))?
+        }
+        _ => return Err(FormatError::UnknownVersion(version)),
+    };
+
+    // Le format 4 n'enregistre que le préfixe du nonce de chaque bloc
+    let nonce_size = match version {
+        FORMAT_VERSION => cipher.nonce_prefix_size(),
+        _ => cipher.nonce_size(),
+    };
+
+    // Le format 1 n'enregistre pas la taille du nonce
+    if version != LEGACY_FORMAT_VERSION {
+        let nonce_len = reader.take(1)?[0] as usize;
+        if nonce_len != nonce_size {
+            return Err(FormatError::BadNonceLength {
+                expected: nonce_size,
+                actual: nonce_len,
+            });
+        }
+    }
+    let nonce = reader.take(nonce_size)?;
+
+    Ok(SlockHeader {
+        version,
+        cipher,
+        nonce,
+        raw: &data[..reader.position],
+    })
+}
+
+/// Bounds-checked cursor over the file content.
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or(FormatError::Truncated {
+                expected: self.position.saturating_add(len),
+                actual: self.data.len(),
+            })?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+}
@@ -0,0 +1,77 @@
+use crate::LockerResult;
+use crate::SmartLockerError;
+use std::io::{self, Write};
+use std::sync::Mutex;
+
+/// How questions to the user (confirmations, passphrases, secret values) are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interaction {
+    /// Ask on the terminal.
+    Prompt,
+    /// Answer yes to every confirmation (`--yes`).
+    AssumeYes,
+    /// Answer no to every confirmation (`--no`, or stdin is not a terminal).
+    AssumeNo,
+    /// Never ask anything: confirmations are declined and passphrases must come
+    /// from the environment or a file descriptor (`--non-interactive`).
+    ///
+    /// This is the default, so library callers are never prompted.
+    #[default]
+    NonInteractive,
+}
+
+impl Interaction {
+    /// Returns `true` if passphrases and secret values may be asked on the terminal.
+    pub fn allows_terminal_input(&self) -> bool {
+        *self != Interaction::NonInteractive
+    }
+}
+
+static INTERACTION: Mutex<Option<Interaction>> = Mutex::new(None);
+
+/// Selects how the user is asked for the rest of the process.
+///
+/// The CLI calls this from `--yes` / `--no` / `--non-interactive`, or from
+/// whether stdin is a terminal. Library code does not read this default: the
+/// policy is passed explicitly, e.g. to [`Locker::open`](crate::Locker::open).
+pub fn set_interaction(interaction: Interaction) {
+    *INTERACTION.lock().unwrap() = Some(interaction);
+}
+
+/// Returns the default interaction policy of the process.
+pub fn interaction() -> Interaction {
+    INTERACTION.lock().unwrap().unwrap_or_default()
+}
+
+/// Asks a yes/no question according to the interaction policy.
+///
+/// Only [`Interaction::Prompt`] reads stdin; the question is written to stderr
+/// so that it never mixes with a secret written to stdout.
+///
+/// # Arguments
+/// * `question` - The question, without the `(yes/no)` suffix.
+///
+/// # Returns
+/// * `Ok(bool)` - The answer.
+/// * `Err(SmartLockerError)` - If stdin cannot be read.
+pub fn confirm(question: &str) -> LockerResult<bool> {
+    match interaction() {
+        Interaction::Prompt => {
+            eprint!("{} (yes/no): ", question);
+            io::stderr().flush().ok();
+
+            // Lire la saisie utilisateur
+            let mut input = String::new();
+            io::stdin().read_line(&mut input).map_err(|e| {
+                SmartLockerError::UnknownError(format!("Error reading user input: {}", e))
+            })?;
+            let input = input.trim().to_lowercase();
+            Ok(input == "yes" || input == "y")
+        }
+        Interaction::AssumeYes => {
+            eprintln!("{} yes (--yes)", question);
+            Ok(true)
+        }
+        Interaction::AssumeNo | Interaction::NonInteractive => Ok(false),
+    }
+}
@@ -0,0 +1,239 @@
+use crate::utils::secret::SecretBytes;
+use crate::utils::toolbox::write_atomic;
+use crate::LockerResult;
+use crate::SmartLockerError;
+use argon2::{Algorithm, Argon2, Params, Version};
+use ring::pbkdf2;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::num::NonZeroU32;
+use std::path::Path;
+use zeroize::Zeroizing;
+
+pub const KEY_PARAMS_FILE: &str = "locker.params"; // Fichier des paramètres de dérivation
+pub const KEY_PARAMS_VERSION: u8 = 1; // Version actuelle du fichier de paramètres
+pub const SALT_SIZE: usize = 16; // Taille du sel aléatoire (16 octets)
+pub const DEFAULT_PBKDF2_ITERATIONS: u32 = 100_000; // Nombre d'itérations PBKDF2 par défaut
+pub const DEFAULT_ARGON2_MEMORY_KIB: u32 = Params::DEFAULT_M_COST; // Mémoire Argon2id (19 MiB)
+pub const DEFAULT_ARGON2_ITERATIONS: u32 = Params::DEFAULT_T_COST; // Passes Argon2id
+pub const DEFAULT_ARGON2_PARALLELISM: u32 = Params::DEFAULT_P_COST; // Threads Argon2id
+                                                                    // Plafonds des coûts acceptés : les paramètres lus sur disque ne sont pas fiables
+pub const MAX_PBKDF2_ITERATIONS: u32 = 10_000_000;
+pub const MAX_ARGON2_MEMORY_KIB: u32 = 1024 * 1024; // 1 GiB
+pub const MAX_ARGON2_ITERATIONS: u32 = 64;
+pub const MAX_ARGON2_PARALLELISM: u32 = 64;
+const LEGACY_SALT: &[u8] = b"smartlocker_salt"; // Sel fixe des anciens lockers
+
+/// Key-derivation function used to turn a passphrase into a key.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "algorithm", rename_all = "kebab-case")]
+pub enum Kdf {
+    /// PBKDF2 with HMAC-SHA256, kept so existing lockers stay readable.
+    Pbkdf2Sha256 { iterations: u32 },
+    /// Memory-hard Argon2id, the default for new lockers.
+    Argon2id {
+        /// Memory cost in KiB.
+        memory_kib: u32,
+        /// Number of passes over the memory.
+        iterations: u32,
+        /// Number of lanes.
+        parallelism: u32,
+    },
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        Kdf::Argon2id {
+            memory_kib: DEFAULT_ARGON2_MEMORY_KIB,
+            iterations: DEFAULT_ARGON2_ITERATIONS,
+            parallelism: DEFAULT_ARGON2_PARALLELISM,
+        }
+    }
+}
+
+impl Kdf {
+    /// Returns a short human-readable name for the algorithm.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kdf::Pbkdf2Sha256 { .. } => "pbkdf2-sha256",
+            Kdf::Argon2id { .. } => "argon2id",
+        }
+    }
+}
+
+/// Versioned key-derivation parameters.
+///
+/// The salt is random per locker, so two lockers sharing a passphrase never
+/// share a key, and the algorithm and cost are recorded so the key can be
+/// re-derived later. They are embedded in the key file; older lockers kept
+/// them in a separate `locker.params` file.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct KeyParams {
+    /// Version of the parameters file format.
+    pub version: u8,
+    /// Key-derivation function and its cost.
+    pub kdf: Kdf,
+    /// Hex-encoded random salt.
+    pub salt: String,
+}
+
+impl KeyParams {
+    /// Creates new parameters for the given KDF with a fresh random salt.
+    pub fn generate(kdf: Kdf) -> Self {
+        let salt = rand::random::<[u8; SALT_SIZE]>();
+        Self {
+            version: KEY_PARAMS_VERSION,
+            kdf,
+            salt: hex::encode(salt),
+        }
+    }
+
+    /// Returns the parameters used by lockers created before they were stored:
+    /// PBKDF2 with 100 000 iterations and a fixed salt.
+    pub fn legacy() -> Self {
+        Self {
+            version: KEY_PARAMS_VERSION,
+            kdf: Kdf::Pbkdf2Sha256 {
+                iterations: DEFAULT_PBKDF2_ITERATIONS,
+            },
+            salt: hex::encode(LEGACY_SALT),
+        }
+    }
+
+    /// Checks that the cost of the KDF is within the supported bounds.
+    ///
+    /// Parameters read from a key file or a backup come from disk: they are
+    /// checked before any key is derived, so a crafted file cannot make
+    /// smart-locker allocate gigabytes of memory or spin for hours.
+    pub fn validate(&self) -> LockerResult<()> {
+        let too_costly = match &self.kdf {
+            Kdf::Pbkdf2Sha256 { iterations } => *iterations > MAX_PBKDF2_ITERATIONS,
+            Kdf::Argon2id {
+                memory_kib,
+                iterations,
+                parallelism,
+            } => {
+                *memory_kib > MAX_ARGON2_MEMORY_KIB
+                    || *iterations > MAX_ARGON2_ITERATIONS
+                    || *parallelism > MAX_ARGON2_PARALLELISM
+            }
+        };
+        if too_costly {
+            return Err(SmartLockerError::InitializationError(format!(
+                "The {} parameters exceed the supported limits ({:?})",
+                self.kdf.name(),
+                self.kdf
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns the decoded salt bytes.
+    pub fn salt_bytes(&self) -> LockerResult<Vec<u8>> {
+        hex::decode(&self.salt).map_err(|e| {
+            SmartLockerError::InitializationError(format!("Invalid salt in key parameters: {}", e))
+        })
+    }
+}
+
+/// Derives a 32-byte key from a passphrase using the given parameters.
+///
+/// # Arguments
+/// * `passphrase` - The passphrase to derive the key from.
+/// * `params` - The KDF algorithm, cost and salt.
+///
+/// # Returns
+/// * `Ok(SecretBytes)` - The derived key, wiped from memory when dropped.
+/// * `Err(SmartLockerError)` - If the parameters are invalid.
+pub fn derive_key(passphrase: &str, params: &KeyParams) -> LockerResult<SecretBytes> {
+    if params.version != KEY_PARAMS_VERSION {
+        return Err(SmartLockerError::InitializationError(format!(
+            "Unsupported key parameters version ({}). Please update smart-locker.",
+            params.version
+        )));
+    }
+    let salt = params.salt_bytes()?;
+    let mut key = Zeroizing::new([0u8; 32]); // 32-byte key
+
+    match &params.kdf {
+        Kdf::Pbkdf2Sha256 { iterations } => {
+            let iterations = NonZeroU32::new(*iterations).ok_or_else(|| {
+                SmartLockerError::InitializationError(
+                    "PBKDF2 iterations must be greater than zero".to_string(),
+                )
+            })?;
+            pbkdf2::derive(
+                pbkdf2::PBKDF2_HMAC_SHA256,
+                iterations,
+                &salt,
+                passphrase.as_bytes(),
+                key.as_mut(),
+            );
+        }
+        Kdf::Argon2id {
+            memory_kib,
+            iterations,
+            parallelism,
+        } => {
+            let argon2_params = Params::new(
+                *memory_kib,
+                *iterations,
+                *parallelism,
+                Some(key.len()),
+            )
+            .map_err(|e| {
+                SmartLockerError::InitializationError(format!("Invalid Argon2id parameters: {}", e))
+            })?;
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, argon2_params)
+                .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+                .map_err(|e| {
+                    SmartLockerError::InitializationError(format!(
+                        "Error deriving key with Argon2id: {}",
+                        e
+                    ))
+                })?;
+        }
+    }
+
+    Ok(SecretBytes::from(&key[..]))
+}
+
+/// Reads the `locker.params` file of a legacy locker, if any.
+///
+/// Lockers created from a random key, before parameters were stored, or with a
+/// wrapped key file have no parameters file, in which case `Ok(None)` is returned.
+pub fn read_key_params(locker_dir: &Path) -> LockerResult<Option<KeyParams>> {
+    let params_path = locker_dir.join(KEY_PARAMS_FILE);
+    if !params_path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(&params_path).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error reading key parameters: {}", e))
+    })?;
+    let params: KeyParams = serde_json::from_str(&content).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error parsing key parameters: {}", e))
+    })?;
+    params.validate()?;
+    Ok(Some(params))
+}
+
+/// Removes the `locker.params` file of a legacy locker, once its key is wrapped:
+/// the key file then records the parameters itself.
+pub(crate) fn remove_key_params(locker_dir: &Path) -> LockerResult<()> {
+    let params_path = locker_dir.join(KEY_PARAMS_FILE);
+    if params_path.exists() {
+        fs::remove_file(&params_path).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error removing stale key parameters: {}", e))
+        })?;
+    }
+    Ok(())
+}
+
+/// Writes the `locker.params` file of a legacy locker.
+pub fn write_key_params(locker_dir: &Path, params: &KeyParams) -> LockerResult<()> {
+    let params_path = locker_dir.join(KEY_PARAMS_FILE);
+    let content = serde_json::to_string_pretty(params).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error serializing key parameters: {}", e))
+    })?;
+    write_atomic(&params_path, content.as_bytes())
+}
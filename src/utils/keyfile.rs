@@ -0,0 +1,236 @@
+use crate::utils::config::EncryptionConfig;
+use crate::utils::interaction::Interaction;
+use crate::utils::kdf::{derive_key, remove_key_params, Kdf, KeyParams};
+use crate::utils::lock::lock_locker;
+use crate::utils::passphrase::{
+    read_confirmed_passphrase_from, read_passphrase_from, PassphraseSource,
+};
+use crate::utils::secret::SecretBytes;
+use crate::utils::toolbox::write_atomic;
+use crate::LockerResult;
+use crate::SmartLockerError;
+use aes_gcm::aead::{Aead, Payload};
+use aes_gcm::Nonce;
+use colored::Colorize;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+pub const KEY_FILE: &str = "locker.key"; // Fichier de la clé du locker
+pub const KEY_FILE_VERSION: u8 = 2; // Version du fichier de clé enveloppée
+pub const ROTATION_JOURNAL: &str = "rotation.journal"; // Journal d'une rotation de clé en cours
+
+/// The locker key file: the data key wrapped under a passphrase-derived key.
+///
+/// The data key never touches the disk in clear text. The key-encryption key
+/// is derived from the passphrase with the recorded KDF parameters and only
+/// lives in memory while the data key is unwrapped.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeyFile {
+    /// Version of the key file format.
+    pub version: u8,
+    /// KDF and salt used to derive the key-encryption key.
+    pub params: KeyParams,
+    /// Hex-encoded nonce used to wrap the data key.
+    pub nonce: String,
+    /// Hex-encoded wrapped data key (AES-256-GCM).
+    pub wrapped_key: String,
+}
+
+/// The key stored in a locker directory.
+pub enum StoredKey {
+    /// A raw 32-byte key written by older versions of smart-locker.
+    Legacy(SecretBytes),
+    /// A data key wrapped under a passphrase.
+    Wrapped(KeyFile),
+}
+
+impl KeyFile {
+    /// Wraps a data key under a key derived from the passphrase with a fresh salt.
+    pub fn wrap(data_key: &SecretBytes, passphrase: &str, kdf: Kdf) -> LockerResult<Self> {
+        let config = EncryptionConfig::new();
+        let params = KeyParams::generate(kdf);
+        params.validate()?;
+        let kek = derive_key(passphrase, &params)?;
+        let cipher = config
+            .init_cipher(&kek)
+            .map_err(SmartLockerError::EncryptionError)?;
+        let nonce = config.generate_nonce();
+        let wrapped_key = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: data_key.expose(),
+                    aad: &Self::associated_data(KEY_FILE_VERSION),
+                },
+            )
+            .map_err(|e| {
+                SmartLockerError::EncryptionError(format!("Error wrapping the key: {}", e))
+            })?;
+
+        Ok(Self {
+            version: KEY_FILE_VERSION,
+            params,
+            nonce: hex::encode(nonce),
+            wrapped_key: hex::encode(wrapped_key),
+        })
+    }
+
+    /// Unwraps the data key with the passphrase.
+    pub fn unwrap_key(&self, passphrase: &str) -> LockerResult<SecretBytes> {
+        if self.version != KEY_FILE_VERSION {
+            return Err(SmartLockerError::DecryptionError(format!(
+                "Unsupported key file version ({}). Please update smart-locker.",
+                self.version
+            )));
+        }
+        let config = EncryptionConfig::new();
+        let nonce = hex::decode(&self.nonce).ok();
+        let wrapped_key = hex::decode(&self.wrapped_key).ok();
+        let (nonce, wrapped_key) = match (nonce, wrapped_key) {
+            (Some(nonce), Some(wrapped_key)) if nonce.len() == config.nonce_size => {
+                (nonce, wrapped_key)
+            }
+            _ => {
+                return Err(SmartLockerError::DecryptionError(
+                    "The key file is corrupted".to_string(),
+                ))
+            }
+        };
+
+        self.params.validate()?;
+        let kek = derive_key(passphrase, &self.params)?;
+        let cipher = config
+            .init_cipher(&kek)
+            .map_err(SmartLockerError::DecryptionError)?;
+        let data_key = cipher
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &wrapped_key,
+                    aad: &Self::associated_data(self.version),
+                },
+            )
+            .map_err(|_| {
+                SmartLockerError::DecryptionError(
+                    "Unable to unlock the key: wrong passphrase?".to_string(),
+                )
+            })?;
+        Ok(SecretBytes::new(data_key))
+    }
+
+    fn associated_data(version: u8) -> Vec<u8> {
+        let mut aad = EncryptionConfig::new().signature.to_vec();
+        aad.push(version);
+        aad
+    }
+}
+
+/// Reads the key file of a locker without unlocking it.
+pub fn read_key_file(locker_dir: &Path) -> LockerResult<StoredKey> {
+    let key_path = locker_dir.join(KEY_FILE);
+    let content = SecretBytes::new(fs::read(&key_path).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Unable to read symmetric key: {}", e))
+    })?);
+
+    // Les anciennes versions écrivaient la clé brute de 32 octets
+    if content.len() == EncryptionConfig::new().key_size {
+        return Ok(StoredKey::Legacy(content));
+    }
+    let key_file = serde_json::from_slice(content.expose()).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error parsing the key file: {}", e))
+    })?;
+    Ok(StoredKey::Wrapped(key_file))
+}
+
+/// Writes the key file of a locker atomically.
+pub fn write_key_file(locker_dir: &Path, key_file: &KeyFile) -> LockerResult<()> {
+    let content = serde_json::to_string_pretty(key_file).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error serializing the key file: {}", e))
+    })?;
+    write_atomic(&locker_dir.join(KEY_FILE), content.as_bytes())
+}
+
+/// Unlocks the data key of a locker, reading the passphrase from `source`.
+///
+/// The returned key is wiped from memory when dropped. The key is not handed
+/// out while a key rotation is in progress, since secrets may be encrypted
+/// under either the old or the new key.
+///
+/// A key still stored in clear text by an older version is wrapped under a
+/// new passphrase first. If no passphrase can be asked for under
+/// `interaction`, a warning is printed and the clear-text key is used as is.
+pub fn load_master_key(
+    locker_dir: &Path,
+    source: &PassphraseSource,
+    interaction: Interaction,
+) -> LockerResult<SecretBytes> {
+    ensure_no_rotation_in_progress(locker_dir)?;
+    match read_key_file(locker_dir)? {
+        StoredKey::Legacy(key) => {
+            if matches!(source, PassphraseSource::Prompt) && !interaction.allows_terminal_input() {
+                warn_legacy_key();
+                return Ok(key);
+            }
+            let passphrase = read_confirmed_passphrase_from(
+                source,
+                interaction,
+                "🔑 The locker key is stored in clear text. Choose a passphrase to protect it: ",
+            )?;
+            wrap_legacy_key(locker_dir, passphrase.expose())
+        }
+        StoredKey::Wrapped(key_file) => {
+            let passphrase = read_passphrase_from(source, interaction, "🔑 Passphrase: ")?;
+            key_file.unwrap_key(passphrase.expose())
+        }
+    }
+}
+
+/// Unlocks the data key of a locker with the given passphrase, without asking for it.
+///
+/// A key still stored in clear text by an older version is wrapped under
+/// `passphrase` first. The locker lock is taken for that, so the caller must
+/// not hold it.
+pub fn unlock_master_key(locker_dir: &Path, passphrase: &str) -> LockerResult<SecretBytes> {
+    ensure_no_rotation_in_progress(locker_dir)?;
+    match read_key_file(locker_dir)? {
+        StoredKey::Legacy(_) => wrap_legacy_key(locker_dir, passphrase),
+        StoredKey::Wrapped(key_file) => key_file.unwrap_key(passphrase),
+    }
+}
+
+/// Wraps a key stored in clear text under `passphrase`, and returns it.
+fn wrap_legacy_key(locker_dir: &Path, passphrase: &str) -> LockerResult<SecretBytes> {
+    let _lock = lock_locker(locker_dir)?;
+    // Relire la clé sous le verrou : un autre processus a pu l'envelopper entre-temps
+    let key = match read_key_file(locker_dir)? {
+        StoredKey::Legacy(key) => key,
+        StoredKey::Wrapped(key_file) => return key_file.unwrap_key(passphrase),
+    };
+    write_key_file(
+        locker_dir,
+        &KeyFile::wrap(&key, passphrase, Kdf::default())?,
+    )?;
+
+    remove_key_params(locker_dir)?;
+    Ok(key)
+}
+
+fn warn_legacy_key() {
+    eprintln!(
+        "{}",
+        "⚠️ The locker key is stored in clear text. Run `smart-locker passwd` or `smart-locker upgrade-kdf` to protect it with a passphrase."
+            .yellow()
+    );
+}
+
+/// Refuses to go on while a key rotation is in progress: `rotate-key` must finish it first.
+pub(crate) fn ensure_no_rotation_in_progress(locker_dir: &Path) -> LockerResult<()> {
+    if locker_dir.join(ROTATION_JOURNAL).exists() {
+        return Err(SmartLockerError::InitializationError(
+            "A key rotation was interrupted. Run `smart-locker rotate-key` to finish it."
+                .to_string(),
+        ));
+    }
+    Ok(())
+}
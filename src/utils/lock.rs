@@ -0,0 +1,53 @@
+use crate::LockerResult;
+use crate::SmartLockerError;
+use std::fs::{self, File, OpenOptions};
+use std::path::Path;
+
+pub const LOCK_FILE: &str = "locker.lock"; // Verrou des écritures dans le locker
+pub const REGISTRY_LOCK_FILE: &str = "vaults.lock"; // Verrou du registre des vaults
+
+/// An advisory exclusive lock on a file, held until it is dropped.
+///
+/// Processes writing to the same locker (parallel CI steps, several shells)
+/// take this lock around every read-modify-write cycle, so none of them
+/// overwrites the index with a stale copy. Readers do not need it: every
+/// file is replaced atomically.
+#[derive(Debug)]
+pub struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    /// Waits until the lock on `path` is free and takes it, creating the file if needed.
+    pub fn acquire(path: &Path) -> LockerResult<Self> {
+        let lock_error = |e: std::io::Error| {
+            SmartLockerError::FileSystemError(format!("Unable to lock {:?}: {}", path, e))
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(lock_error)?;
+        }
+        let mut options = OpenOptions::new();
+        options.read(true).write(true).create(true).truncate(false);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options.open(path).map_err(lock_error)?;
+        file.lock().map_err(lock_error)?;
+        Ok(Self { file })
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Takes the write lock of the locker in `locker_dir`.
+///
+/// The lock is not reentrant: a process must not take it twice.
+pub fn lock_locker(locker_dir: &Path) -> LockerResult<FileLock> {
+    FileLock::acquire(&locker_dir.join(LOCK_FILE))
+}
@@ -1,102 +1,276 @@
-use crate::utils::toolbox::get_locker_dir;
+use crate::utils::config::{Cipher, LockerConfig};
+use crate::utils::crypto::{open_secret, seal_secret};
+use crate::utils::secret::SecretBytes;
+use crate::utils::toolbox::{get_locker_dir, is_this_secret, write_atomic};
+use crate::LockerResult;
 use crate::MetadataFile;
 use crate::SecretMetadata;
+use crate::SecretVersion;
 use crate::SmartLockerError;
 use colored::Colorize;
-use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fs;
+use std::path::{Component, Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-/// Initializes the metadata file if it does not already exist.
+pub const METADATA_INDEX_FILE: &str = "metadata.index"; // Index des métadonnées, chiffré
+pub const LEGACY_METADATA_FILE: &str = "metadata.json"; // Ancien index en clair
+pub const SECRET_ID_SIZE: usize = 16; // Taille de l'identifiant opaque des fichiers (16 octets)
+const INDEX_AAD_NAME: &str = "\0metadata-index"; // Nom authentifié de l'index, impossible pour un secret
+
+/// Generates a random opaque identifier for a secret file.
+pub fn generate_secret_id() -> String {
+    hex::encode(rand::random::<[u8; SECRET_ID_SIZE]>())
+}
+
+/// Returns the path of the `.slock` file holding a secret.
+///
+/// Files are named after an opaque identifier recorded in the sealed index,
+/// so the directory listing does not reveal which secrets the locker holds.
+pub fn secret_file_path(locker_dir: &Path, secret_metadata: &SecretMetadata) -> PathBuf {
+    locker_dir.join(format!("{}.slock", secret_metadata.id))
+}
+
+/// Lists every file the index refers to, with the name its content is bound to:
+/// the current value and previous versions of each secret, in use or in the trash.
+pub fn indexed_secret_files(locker_dir: &Path, metadata: &MetadataFile) -> Vec<(String, PathBuf)> {
+    metadata
+        .secrets
+        .values()
+        .chain(metadata.trash.iter().map(|trashed| trashed.secret()))
+        .filter(|secret| !secret.id.is_empty())
+        .flat_map(|secret| {
+            secret.versions().into_iter().map(|version| {
+                (
+                    secret.name.clone(),
+                    secret_version_path(locker_dir, &version),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Returns the path of the `.slock` file holding a previous version of a secret.
+pub fn secret_version_path(locker_dir: &Path, version: &SecretVersion) -> PathBuf {
+    locker_dir.join(format!("{}.slock", version.id))
+}
+
+/// Encrypts the metadata index with the locker key and cipher.
+pub fn seal_metadata(
+    metadata: &MetadataFile,
+    key_data: &SecretBytes,
+    cipher: Cipher,
+) -> LockerResult<Vec<u8>> {
+    let content = serde_json::to_vec(metadata).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error serializing metadata: {}", e))
+    })?;
+    seal_secret(INDEX_AAD_NAME, &content, key_data, cipher)
+}
+
+/// Decrypts the metadata index with the locker key.
+pub fn open_metadata(data: &[u8], key_data: &SecretBytes) -> LockerResult<MetadataFile> {
+    let content = open_secret(INDEX_AAD_NAME, data, key_data)?;
+    serde_json::from_slice(content.expose()).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error parsing metadata file: {}", e))
+    })
+}
+
+/// Initializes an empty metadata index sealed with the locker key.
 ///
-/// This function creates a `metadata.json` file in the locker directory with an empty structure.
-/// If the file already exists, it does nothing.
+/// This function is called when a new key is generated: an index sealed with a
+/// previous key could not be opened anymore, so it is replaced.
 ///
 /// # Returns
-/// - `Ok(())` if the metadata file is successfully initialized or already exists.
+/// - `Ok(())` if the metadata index is successfully initialized.
 /// - `Err(SmartLockerError)` if there is an error during initialization.
 ///
 /// # Example
-/// ```
+/// ```no_run
 /// use smart_locker::utils::metadata::init_metadata_file;
+/// use smart_locker::SecretBytes;
 ///
-/// match init_metadata_file() {
+/// let key_data = SecretBytes::new(vec![0u8; 32]);
+/// match init_metadata_file(&key_data) {
 ///     Ok(_) => println!("Metadata file initialized successfully."),
 ///     Err(e) => eprintln!("Failed to initialize metadata file: {}", e),
 /// }
 /// ```
-pub fn init_metadata_file() -> Result<(), SmartLockerError> {
-    let metadata_path = get_locker_dir()?.join("metadata.json");
-    if !metadata_path.exists() {
-        let empty_metadata = MetadataFile {
-            secrets: Default::default(),
-        };
-        let metadata_json = serde_json::to_string_pretty(&empty_metadata).map_err(|e| {
-            SmartLockerError::FileSystemError(format!("Error serializing metadata: {}", e))
-        })?;
-        fs::write(metadata_path.clone(), metadata_json).map_err(|e| {
-            SmartLockerError::FileSystemError(format!("Error writing metadata file: {}", e))
-        })?;
-        println!("✅ Metadata file initialized: {:?}", metadata_path);
-    } else {
-        println!("🔍 Metadata file already exists: {:?}", metadata_path);
-    }
+pub fn init_metadata_file(key_data: &SecretBytes) -> Result<(), SmartLockerError> {
+    let empty_metadata = MetadataFile::default();
+    write_metadata(&empty_metadata, key_data)?;
     Ok(())
 }
 
-/// Reads the metadata file and returns its contents as a `MetadataFile` object.
+/// Reads the sealed metadata index and returns its contents as a `MetadataFile` object.
 ///
-/// If the metadata file does not exist, it returns an empty `MetadataFile` structure.
+/// If the index does not exist, it returns an empty `MetadataFile` structure.
+/// A clear-text `metadata.json` left by an older version is encrypted into the
+/// index on the fly, and its secret files are renamed to opaque identifiers.
 ///
 /// # Returns
 /// - `Ok(MetadataFile)` containing the metadata.
-/// - `Err(SmartLockerError)` if there is an error reading or parsing the file.
+/// - `Err(SmartLockerError)` if there is an error reading, decrypting or parsing the index.
 ///
 /// # Example
-/// ```
+/// ```no_run
 /// use smart_locker::utils::metadata::read_metadata;
+/// use smart_locker::SecretBytes;
 ///
-/// match read_metadata() {
+/// let key_data = SecretBytes::new(vec![0u8; 32]);
+/// match read_metadata(&key_data) {
 ///     Ok(metadata) => println!("Metadata loaded: {:?}", metadata),
 ///     Err(e) => eprintln!("Failed to read metadata: {}", e),
 /// }
 /// ```
-pub fn read_metadata() -> Result<MetadataFile, SmartLockerError> {
-    let locker_dir = get_locker_dir()?;
-    let metadata_path = locker_dir.join("metadata.json");
-    if !metadata_path.exists() {
-        return Ok(MetadataFile {
-            secrets: HashMap::new(),
-        });
+pub fn read_metadata(key_data: &SecretBytes) -> Result<MetadataFile, SmartLockerError> {
+    read_metadata_in(&get_locker_dir()?, key_data)
+}
+
+/// Reads the sealed metadata index of the locker in `locker_dir`.
+///
+/// Nothing is printed: callers can check [`has_legacy_metadata`] beforehand
+/// to know if a `metadata.json` was sealed.
+pub fn read_metadata_in(locker_dir: &Path, key_data: &SecretBytes) -> LockerResult<MetadataFile> {
+    let metadata_path = locker_dir.join(METADATA_INDEX_FILE);
+    let legacy_path = locker_dir.join(LEGACY_METADATA_FILE);
+
+    let mut metadata = if metadata_path.exists() {
+        let data = fs::read(&metadata_path).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error reading metadata file: {}", e))
+        })?;
+        open_metadata(&data, key_data)?
+    } else if legacy_path.exists() {
+        let content = fs::read_to_string(&legacy_path).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error reading metadata file: {}", e))
+        })?;
+        serde_json::from_str(&content).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error parsing metadata file: {}", e))
+        })?
+    } else {
+        MetadataFile::default()
+    };
+
+    if legacy_path.exists() {
+        seal_legacy_metadata(locker_dir, &mut metadata, key_data)?;
     }
+    Ok(metadata)
+}
 
-    let content = fs::read_to_string(&metadata_path).map_err(|e| {
-        SmartLockerError::FileSystemError(format!("Error reading metadata file: {}", e))
-    })?;
-    serde_json::from_str(&content).map_err(|e| {
-        SmartLockerError::FileSystemError(format!("Error parsing metadata file: {}", e))
-    })
+pub fn write_metadata(
+    metadata: &MetadataFile,
+    key_data: &SecretBytes,
+) -> Result<(), SmartLockerError> {
+    write_metadata_in(&get_locker_dir()?, metadata, key_data)
 }
 
-pub fn write_metadata(metadata: &MetadataFile) -> Result<(), SmartLockerError> {
-    let locker_dir = get_locker_dir()?;
-    let metadata_path = locker_dir.join("metadata.json");
-    let content = serde_json::to_string_pretty(metadata).map_err(|e| {
-        SmartLockerError::FileSystemError(format!("Error serializing metadata: {}", e))
+/// Seals and writes the metadata index of the locker in `locker_dir`, with the locker cipher.
+pub fn write_metadata_in(
+    locker_dir: &Path,
+    metadata: &MetadataFile,
+    key_data: &SecretBytes,
+) -> LockerResult<()> {
+    let metadata_path = locker_dir.join(METADATA_INDEX_FILE);
+    let cipher = LockerConfig::load(locker_dir)?.cipher;
+    write_atomic(&metadata_path, &seal_metadata(metadata, key_data, cipher)?)
+}
+
+/// Returns `true` if the locker still has a clear-text `metadata.json`.
+pub fn has_legacy_metadata(locker_dir: &Path) -> bool {
+    locker_dir.join(LEGACY_METADATA_FILE).exists()
+}
+
+/// Encrypts a clear-text `metadata.json` into the index and hides the file names.
+///
+/// The index is written first, then the files are renamed, then `metadata.json`
+/// is removed: an interrupted upgrade is finished by the next read.
+fn seal_legacy_metadata(
+    locker_dir: &Path,
+    metadata: &mut MetadataFile,
+    key_data: &SecretBytes,
+) -> LockerResult<()> {
+    // Les anciens fichiers portaient le nom du secret : ceux qui sortiraient du locker
+    // gardent un état hérité, sans identifiant
+    for (name, secret_metadata) in metadata.secrets.iter_mut() {
+        if secret_metadata.id.is_empty() && is_plain_file_name(name) {
+            secret_metadata.id = generate_secret_id();
+        }
+    }
+    write_metadata_in(locker_dir, metadata, key_data)?;
+
+    for (name, secret_metadata) in metadata.secrets.iter() {
+        if secret_metadata.id.is_empty() {
+            continue;
+        }
+        let legacy_path = locker_dir.join(format!("{}.slock", name));
+        let secret_path = secret_file_path(locker_dir, secret_metadata);
+        if legacy_path.exists() && !secret_path.exists() {
+            fs::rename(&legacy_path, &secret_path).map_err(|e| {
+                SmartLockerError::FileSystemError(format!(
+                    "Error renaming the file of secret '{}': {}",
+                    name, e
+                ))
+            })?;
+        }
+    }
+
+    fs::remove_file(locker_dir.join(LEGACY_METADATA_FILE)).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error removing metadata.json: {}", e))
     })?;
-    fs::write(metadata_path, content).map_err(|e| {
-        SmartLockerError::FileSystemError(format!("Error writing metadata file: {}", e))
-    })
+    Ok(())
+}
+
+/// Returns `true` if `<name>.slock` is a file directly in the locker directory.
+///
+/// Older versions accepted any name, e.g. with spaces or `@`: the grammar of
+/// [`validate_secret_name`](crate::utils::name::validate_secret_name) does not
+/// apply, only names that would escape the directory are refused.
+fn is_plain_file_name(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    !name.contains(['/', '\\'])
+        && matches!(
+            (components.next(), components.next()),
+            (Some(Component::Normal(_)), None)
+        )
+}
+
+/// Lists the `.slock` files that are not referenced by the index.
+///
+/// These are secrets written by older versions, still named `<name>.slock`,
+/// whose metadata must be migrated.
+pub fn find_unindexed_secret_files(
+    locker_dir: &Path,
+    metadata: &MetadataFile,
+) -> LockerResult<Vec<(String, PathBuf)>> {
+    let indexed: HashSet<PathBuf> = indexed_secret_files(locker_dir, metadata)
+        .into_iter()
+        .map(|(_, path)| path)
+        .collect();
+    let mut unindexed = Vec::new();
+    for entry in fs::read_dir(locker_dir).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error reading locker directory: {}", e))
+    })? {
+        let entry = entry.map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error reading directory entry: {}", e))
+        })?;
+        let path = entry.path();
+        if let (true, Some(stem)) = is_this_secret(&path, true) {
+            if !indexed.contains(&path) {
+                unindexed.push((stem, path));
+            }
+        }
+    }
+    Ok(unindexed)
 }
 
 pub fn remove_metadata(
     secret_name: Option<&str>,
     metadata: &mut MetadataFile,
+    key_data: &SecretBytes,
 ) -> Result<(), SmartLockerError> {
     match secret_name {
         Some(name) => {
             if metadata.secrets.remove(name).is_some() {
-                write_metadata(metadata)?;
+                write_metadata(metadata, key_data)?;
                 Ok(())
             } else {
                 Err(SmartLockerError::FileSystemError(format!(
@@ -108,7 +282,7 @@ pub fn remove_metadata(
         None => {
             // Supprimer toutes les métadonnées
             metadata.secrets.clear();
-            write_metadata(metadata)?;
+            write_metadata(metadata, key_data)?;
             Ok(())
         }
     }
@@ -117,6 +291,7 @@ pub fn remove_metadata(
 pub fn update_secret_metadata<F>(
     secret_name: &str,
     metadata: &mut MetadataFile,
+    key_data: &SecretBytes,
     update_fn: F,
 ) -> Result<(), SmartLockerError>
 where
@@ -124,7 +299,7 @@ where
 {
     if let Some(secret_metadata) = metadata.secrets.get_mut(secret_name) {
         update_fn(secret_metadata);
-        write_metadata(metadata)?;
+        write_metadata(metadata, key_data)?;
         Ok(())
     } else {
         Err(SmartLockerError::FileSystemError(format!(
@@ -136,20 +311,26 @@ where
 
 pub fn has_metadata_file() -> bool {
     let locker_dir = get_locker_dir().unwrap();
-    let metadata_path = locker_dir.join("metadata.json");
-    metadata_path.exists()
+    locker_dir.join(METADATA_INDEX_FILE).exists() || locker_dir.join(LEGACY_METADATA_FILE).exists()
 }
 
 pub fn metadata_field_count(secret_metadata: &SecretMetadata) -> usize {
     SecretMetadata::field_count(Some(secret_metadata))
 }
 
-pub fn has_this_secret_metadata(secret_name: &str, metadata: &MetadataFile) -> bool {
-    if let Some(secret_metadata) = metadata.secrets.get(secret_name) {
-        let expected_field_count = SecretMetadata::field_count(None);
-        let actual_field_count = SecretMetadata::field_count(Some(secret_metadata));
+/// Returns `true` if the index holds up-to-date metadata for the secret.
+pub fn is_metadata_current(secret_name: &str, metadata: &MetadataFile) -> bool {
+    metadata
+        .secrets
+        .get(secret_name)
+        .is_some_and(|secret_metadata| {
+            SecretMetadata::field_count(Some(secret_metadata)) == SecretMetadata::field_count(None)
+        })
+}
 
-        if actual_field_count == expected_field_count {
+pub fn has_this_secret_metadata(secret_name: &str, metadata: &MetadataFile) -> bool {
+    if metadata.secrets.contains_key(secret_name) {
+        if is_metadata_current(secret_name, metadata) {
             true // Métadonnées valides
         } else {
             println!(
@@ -178,8 +359,9 @@ pub fn has_this_secret_metadata(secret_name: &str, metadata: &MetadataFile) -> b
 pub fn mark_secret_as_expired(
     secret_name: &str,
     metadata: &mut MetadataFile,
+    key_data: &SecretBytes,
 ) -> Result<(), SmartLockerError> {
-    update_secret_metadata(secret_name, metadata, |secret_metadata| {
+    update_secret_metadata(secret_name, metadata, key_data, |secret_metadata| {
         secret_metadata.expired = true;
     })
 }
@@ -195,9 +377,10 @@ pub fn is_secret_expired(secret_metadata: &SecretMetadata) -> bool {
 pub fn update_secret_expiration(
     secret_name: &str,
     metadata: &mut MetadataFile,
+    key_data: &SecretBytes,
     new_expiration: u64,
 ) -> Result<(), SmartLockerError> {
-    update_secret_metadata(secret_name, metadata, |secret_metadata| {
+    update_secret_metadata(secret_name, metadata, key_data, |secret_metadata| {
         secret_metadata.expire_at = new_expiration;
         secret_metadata.expired = false; // Marquer comme non expiré
     })
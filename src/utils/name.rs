@@ -0,0 +1,58 @@
+use crate::LockerResult;
+use crate::SmartLockerError;
+
+pub const NAMESPACE_SEPARATOR: char = '/'; // Sépare les namespaces : `prod/db/password`
+pub const MAX_SECRET_NAME_LEN: usize = 255; // Longueur maximale d'un nom, en octets
+
+/// Checks that a secret name follows the name grammar.
+///
+/// A name is one or more segments separated by `/`, such as `prod/db/password`.
+/// Each segment is made of ASCII letters, digits, `_`, `-` and `.`, and is
+/// neither `.` nor `..`. The whole name is at most 255 bytes long.
+///
+/// Names never reach the file system (secret files are named after an opaque
+/// identifier), but a strict grammar keeps them usable in shells, `.env` files
+/// and prefix filters.
+pub fn validate_secret_name(name: &str) -> LockerResult<()> {
+    let invalid = |reason: &str| {
+        Err(SmartLockerError::InvalidSecretName(format!(
+            "'{}': {}",
+            name, reason
+        )))
+    };
+
+    if name.is_empty() {
+        return invalid("the name is empty");
+    }
+    if name.len() > MAX_SECRET_NAME_LEN {
+        return invalid(&format!(
+            "the name is longer than {} bytes",
+            MAX_SECRET_NAME_LEN
+        ));
+    }
+    for segment in name.split(NAMESPACE_SEPARATOR) {
+        if segment.is_empty() {
+            return invalid("namespaces cannot be empty (leading, trailing or double '/')");
+        }
+        if segment == "." || segment == ".." {
+            return invalid("'.' and '..' are not allowed as a namespace");
+        }
+        if let Some(c) = segment
+            .chars()
+            .find(|c| !(c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.')))
+        {
+            return invalid(&format!(
+                "'{}' is not allowed, use letters, digits, '_', '-', '.' and '/'",
+                c.escape_default()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Turns a secret name into an environment variable name: `prod/db.password` gives `prod_db_password`.
+pub fn env_var_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
@@ -0,0 +1,244 @@
+use crate::utils::interaction::{interaction, Interaction};
+use crate::utils::secret::SecretString;
+use crate::LockerResult;
+use crate::SmartLockerError;
+use std::env;
+use std::sync::Mutex;
+
+pub const PASSPHRASE_ENV: &str = "SMART_LOCKER_PASSPHRASE"; // Passphrase fournie par l'environnement
+pub const PASSPHRASE_FD_ENV: &str = "SMART_LOCKER_PASSPHRASE_FD"; // Descripteur de fichier à lire
+pub const NEW_PASSPHRASE_ENV: &str = "SMART_LOCKER_NEW_PASSPHRASE"; // Nouvelle passphrase pour `passwd`
+
+/// Where the passphrase protecting the locker key is read from.
+#[derive(Clone, Debug)]
+pub enum PassphraseSource {
+    /// Ask on the terminal.
+    Prompt,
+    /// Read the named environment variable.
+    Env(String),
+    /// Read the first line of an open file descriptor (Unix only).
+    ///
+    /// A descriptor can only be read once: [`passphrase_source`] reads it as
+    /// soon as it is selected, and keeps the passphrase as a
+    /// [`PassphraseSource::Value`] for the rest of the process.
+    Fd(i32),
+    /// A passphrase already known, e.g. read from a file descriptor.
+    Value(SecretString),
+}
+
+static PASSPHRASE_SOURCE: Mutex<Option<PassphraseSource>> = Mutex::new(None);
+
+/// Selects the default passphrase source for the rest of the process.
+///
+/// The CLI calls this from `--passphrase-env` / `--passphrase-fd`. Library code
+/// does not read this default: a source is passed explicitly, e.g. to
+/// [`Locker::open`](crate::Locker::open).
+pub fn set_passphrase_source(source: PassphraseSource) {
+    *PASSPHRASE_SOURCE.lock().unwrap() = Some(source);
+}
+
+/// Returns the default passphrase source of the process.
+///
+/// A source set with [`set_passphrase_source`] wins. Otherwise
+/// `SMART_LOCKER_PASSPHRASE` and then `SMART_LOCKER_PASSPHRASE_FD` are used if
+/// they are defined, and the user is prompted as a last resort, unless the
+/// [interaction policy](crate::utils::interaction) forbids it.
+pub fn passphrase_source() -> LockerResult<PassphraseSource> {
+    let selected = PASSPHRASE_SOURCE.lock().unwrap().clone();
+    let source = match selected {
+        Some(source) => source,
+        None if env::var_os(PASSPHRASE_ENV).is_some() => {
+            PassphraseSource::Env(PASSPHRASE_ENV.to_string())
+        }
+        None => match env::var(PASSPHRASE_FD_ENV) {
+            Ok(fd) => PassphraseSource::Fd(fd.trim().parse().map_err(|_| {
+                SmartLockerError::InitializationError(format!(
+                    "Invalid file descriptor in {}: '{}'",
+                    PASSPHRASE_FD_ENV, fd
+                ))
+            })?),
+            Err(_) => PassphraseSource::Prompt,
+        },
+    };
+
+    // Un descripteur ne se relit pas : le lire une fois et garder la passphrase
+    if let PassphraseSource::Fd(fd) = source {
+        let passphrase = read_passphrase_from_fd(fd)?;
+        set_passphrase_source(PassphraseSource::Value(passphrase.clone()));
+        return Ok(PassphraseSource::Value(passphrase));
+    }
+    Ok(source)
+}
+
+/// Reads the passphrase from the default source of the process.
+///
+/// # Arguments
+/// * `prompt` - The message displayed when the passphrase is asked on the terminal.
+///
+/// # Returns
+/// * `Ok(SecretString)` - The passphrase, wiped from memory when dropped.
+/// * `Err(SmartLockerError)` - If the source cannot be read or the passphrase is empty.
+pub fn read_passphrase(prompt: &str) -> LockerResult<SecretString> {
+    read_passphrase_from(&passphrase_source()?, interaction(), prompt)
+}
+
+/// Reads a passphrase being chosen, e.g. when a vault is created.
+///
+/// Like [`read_passphrase`], but a passphrase typed on the terminal must be
+/// entered twice, so a typo cannot lock the user out of the vault.
+pub fn read_confirmed_passphrase(prompt: &str) -> LockerResult<SecretString> {
+    read_confirmed_passphrase_from(&passphrase_source()?, interaction(), prompt)
+}
+
+/// Reads the passphrase from an explicit source, with an explicit interaction policy.
+///
+/// This is what [`read_passphrase`] does with the defaults of the process.
+pub fn read_passphrase_from(
+    source: &PassphraseSource,
+    interaction: Interaction,
+    prompt: &str,
+) -> LockerResult<SecretString> {
+    read_from_source(source, interaction, prompt, false)
+}
+
+/// Reads a passphrase being chosen from an explicit source, with an explicit
+/// interaction policy, like [`read_confirmed_passphrase`].
+pub fn read_confirmed_passphrase_from(
+    source: &PassphraseSource,
+    interaction: Interaction,
+    prompt: &str,
+) -> LockerResult<SecretString> {
+    read_from_source(source, interaction, prompt, true)
+}
+
+fn read_from_source(
+    source: &PassphraseSource,
+    interaction: Interaction,
+    prompt: &str,
+    confirm: bool,
+) -> LockerResult<SecretString> {
+    let passphrase = match source {
+        PassphraseSource::Prompt if confirm => {
+            prompt_twice(interaction, prompt, "🔑 Confirm the passphrase: ")?
+        }
+        PassphraseSource::Prompt => prompt_hidden(interaction, prompt)?,
+        PassphraseSource::Env(var) => read_passphrase_env(var)?,
+        PassphraseSource::Fd(fd) => read_passphrase_from_fd(*fd)?,
+        PassphraseSource::Value(passphrase) => passphrase.clone(),
+    };
+
+    if passphrase.is_empty() {
+        return Err(SmartLockerError::InitializationError(
+            "The passphrase cannot be empty".to_string(),
+        ));
+    }
+    Ok(passphrase)
+}
+
+/// Reads a passphrase from the named environment variable, e.g. the passphrase
+/// of a backup given with `--backup-passphrase-env`.
+pub fn read_passphrase_env(var: &str) -> LockerResult<SecretString> {
+    let passphrase = SecretString::new(env::var(var).map_err(|_| {
+        SmartLockerError::InitializationError(format!(
+            "Environment variable '{}' is not set or not valid UTF-8",
+            var
+        ))
+    })?);
+    if passphrase.is_empty() {
+        return Err(SmartLockerError::InitializationError(
+            "The passphrase cannot be empty".to_string(),
+        ));
+    }
+    Ok(passphrase)
+}
+
+/// Reads a new passphrase, e.g. when changing it with `passwd`.
+///
+/// `SMART_LOCKER_NEW_PASSPHRASE` is used if it is defined, otherwise the user
+/// is prompted twice.
+pub fn read_new_passphrase(prompt: &str) -> LockerResult<SecretString> {
+    let passphrase = match env::var(NEW_PASSPHRASE_ENV) {
+        Ok(passphrase) => SecretString::new(passphrase),
+        Err(_) => prompt_new_secret(prompt, "🔑 Confirm the new passphrase: ")?,
+    };
+
+    if passphrase.is_empty() {
+        return Err(SmartLockerError::InitializationError(
+            "The passphrase cannot be empty".to_string(),
+        ));
+    }
+    Ok(passphrase)
+}
+
+/// Asks for a new secret on the terminal, twice, without echoing it.
+///
+/// # Arguments
+/// * `prompt` - The message displayed for the first entry.
+/// * `confirm_prompt` - The message displayed for the confirmation.
+///
+/// # Returns
+/// * `Ok(SecretString)` - The secret, wiped from memory when dropped.
+/// * `Err(SmartLockerError)` - If the terminal cannot be read or the entries differ.
+pub fn prompt_new_secret(prompt: &str, confirm_prompt: &str) -> LockerResult<SecretString> {
+    prompt_twice(interaction(), prompt, confirm_prompt)
+}
+
+fn prompt_twice(
+    interaction: Interaction,
+    prompt: &str,
+    confirm_prompt: &str,
+) -> LockerResult<SecretString> {
+    let secret = prompt_hidden(interaction, prompt)?;
+    let confirmation = prompt_hidden(interaction, confirm_prompt)?;
+    if secret.expose() != confirmation.expose() {
+        return Err(SmartLockerError::InitializationError(
+            "The two entries do not match".to_string(),
+        ));
+    }
+    Ok(secret)
+}
+
+// Lit une saisie sur le terminal sans l'afficher, si la politique d'interaction le permet
+fn prompt_hidden(interaction: Interaction, prompt: &str) -> LockerResult<SecretString> {
+    if !interaction.allows_terminal_input() {
+        return Err(SmartLockerError::InitializationError(format!(
+            "Cannot prompt in non-interactive mode. Set {} or use --passphrase-env / --passphrase-fd, \
+            and pass secret values through --value, --file or stdin",
+            PASSPHRASE_ENV
+        )));
+    }
+    rpassword::prompt_password(prompt)
+        .map(SecretString::new)
+        .map_err(|e| SmartLockerError::InitializationError(format!("Error reading input: {}", e)))
+}
+
+#[cfg(unix)]
+fn read_passphrase_from_fd(fd: i32) -> LockerResult<SecretString> {
+    use std::fs::File;
+    use std::io::{BufRead, BufReader};
+    use std::mem::ManuallyDrop;
+    use std::os::unix::io::FromRawFd;
+    use zeroize::Zeroizing;
+
+    // Le descripteur appartient à l'appelant : l'emprunter sans jamais le fermer
+    let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+    let mut line = Zeroizing::new(String::new());
+    BufReader::new(&mut *file)
+        .read_line(&mut line)
+        .map_err(|e| {
+            SmartLockerError::InitializationError(format!(
+                "Error reading passphrase from file descriptor {}: {}",
+                fd, e
+            ))
+        })?;
+    Ok(SecretString::new(
+        line.trim_end_matches(['\n', '\r']).to_string(),
+    ))
+}
+
+#[cfg(not(unix))]
+fn read_passphrase_from_fd(_fd: i32) -> LockerResult<SecretString> {
+    Err(SmartLockerError::InitializationError(
+        "Reading the passphrase from a file descriptor is only supported on Unix".to_string(),
+    ))
+}
@@ -0,0 +1,121 @@
+use std::fmt;
+use zeroize::Zeroizing;
+
+const REDACTED: &str = "[REDACTED]"; // Affiché à la place du contenu d'un secret
+
+/// Secret bytes (a key, a decrypted value), wiped from memory when dropped.
+///
+/// `Debug` and `Display` never print the content, so a secret cannot leak into
+/// logs or error messages by accident: it must be read with [`SecretBytes::expose`].
+#[derive(Clone, Default)]
+pub struct SecretBytes(Zeroizing<Vec<u8>>);
+
+impl SecretBytes {
+    /// Takes ownership of the bytes; they are wiped when the value is dropped.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(Zeroizing::new(bytes))
+    }
+
+    /// Gives read access to the secret bytes.
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the number of bytes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the secret is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl From<&[u8]> for SecretBytes {
+    fn from(bytes: &[u8]) -> Self {
+        Self::new(bytes.to_vec())
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes({})", REDACTED)
+    }
+}
+
+impl fmt::Display for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+/// A secret UTF-8 string (a decrypted value, a passphrase), wiped from memory when dropped.
+///
+/// Like [`SecretBytes`], its `Debug` and `Display` are redacted and the content
+/// is only reachable through [`SecretString::expose`].
+#[derive(Clone, Default)]
+pub struct SecretString(Zeroizing<String>);
+
+impl SecretString {
+    /// Takes ownership of the string; it is wiped when the value is dropped.
+    pub fn new(value: String) -> Self {
+        Self(Zeroizing::new(value))
+    }
+
+    /// Converts secret bytes to a string without copying them.
+    ///
+    /// The bytes are handed back, still protected, if they are not valid UTF-8.
+    pub fn from_utf8(mut bytes: SecretBytes) -> Result<Self, SecretBytes> {
+        let raw = std::mem::take(&mut *bytes.0);
+        match String::from_utf8(raw) {
+            Ok(value) => Ok(Self::new(value)),
+            Err(err) => Err(SecretBytes::new(err.into_bytes())),
+        }
+    }
+
+    /// Gives read access to the secret string.
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the length in bytes.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns `true` if the secret is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self::new(value.to_string())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretString({})", REDACTED)
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
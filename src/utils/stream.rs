@@ -0,0 +1,226 @@
+use crate::utils::config::{Cipher, STREAM_CHUNK_SIZE, STREAM_NONCE_SUFFIX_SIZE, TAG_SIZE};
+use crate::utils::secret::SecretBytes;
+use aes_gcm::aead::Payload;
+use std::io::{self, Read, Write};
+use zeroize::Zeroizing;
+
+/// Chunked encryption of a `.slock` payload (STREAM construction).
+///
+/// The plaintext is cut into chunks of [`STREAM_CHUNK_SIZE`] bytes, each sealed
+/// separately with the nonce `prefix | counter (u32, big endian) | last flag`.
+/// The counter prevents chunks from being reordered or dropped, and the flag
+/// on the final chunk makes a truncated file fail to authenticate.
+pub struct StreamEncryptor<'a, W: Write> {
+    writer: W,
+    cipher: Cipher,
+    key: &'a SecretBytes,
+    nonce_prefix: Vec<u8>,
+    aad: Vec<u8>,
+    buffer: Zeroizing<Vec<u8>>,
+    counter: u32,
+}
+
+impl<'a, W: Write> StreamEncryptor<'a, W> {
+    /// Creates an encryptor writing sealed chunks to `writer`.
+    ///
+    /// `aad` is authenticated with every chunk (the `.slock` header and the secret name).
+    pub fn new(
+        writer: W,
+        cipher: Cipher,
+        key: &'a SecretBytes,
+        nonce_prefix: Vec<u8>,
+        aad: Vec<u8>,
+    ) -> Self {
+        Self {
+            writer,
+            cipher,
+            key,
+            nonce_prefix,
+            aad,
+            buffer: Zeroizing::new(Vec::with_capacity(STREAM_CHUNK_SIZE + 1)),
+            counter: 0,
+        }
+    }
+
+    /// Seals the remaining data as the final chunk and returns the writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let len = self.buffer.len();
+        self.seal_chunk(len, true)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn seal_chunk(&mut self, len: usize, last: bool) -> io::Result<()> {
+        let nonce = chunk_nonce(&self.nonce_prefix, self.counter, last);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                self.key.expose(),
+                &nonce,
+                Payload {
+                    msg: &self.buffer[..len],
+                    aad: &self.aad,
+                },
+            )
+            .map_err(io::Error::other)?;
+        self.writer.write_all(&ciphertext)?;
+        self.buffer.drain(..len);
+        if !last {
+            self.counter = self
+                .counter
+                .checked_add(1)
+                .ok_or_else(|| io::Error::other("The secret is too large to be encrypted"))?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for StreamEncryptor<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Un bloc n'est scellé qu'une fois suivi d'au moins un octet : le dernier reste en attente de `finish`
+        let len = buf.len().min(STREAM_CHUNK_SIZE + 1 - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() > STREAM_CHUNK_SIZE {
+            self.seal_chunk(STREAM_CHUNK_SIZE, false)?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Chunked decryption of a `.slock` payload, the counterpart of [`StreamEncryptor`].
+///
+/// Only authenticated chunks are returned. A chunk that fails to authenticate
+/// is reported as an [`io::ErrorKind::InvalidData`] error.
+pub struct StreamDecryptor<'a, R: Read> {
+    reader: R,
+    cipher: Cipher,
+    key: &'a SecretBytes,
+    nonce_prefix: Vec<u8>,
+    aad: Vec<u8>,
+    counter: u32,
+    lookahead: Option<u8>,
+    plaintext: Zeroizing<Vec<u8>>,
+    position: usize,
+    finished: bool,
+}
+
+impl<'a, R: Read> StreamDecryptor<'a, R> {
+    /// Creates a decryptor reading sealed chunks from `reader`.
+    pub fn new(
+        reader: R,
+        cipher: Cipher,
+        key: &'a SecretBytes,
+        nonce_prefix: Vec<u8>,
+        aad: Vec<u8>,
+    ) -> Self {
+        Self {
+            reader,
+            cipher,
+            key,
+            nonce_prefix,
+            aad,
+            counter: 0,
+            lookahead: None,
+            plaintext: Zeroizing::new(Vec::new()),
+            position: 0,
+            finished: false,
+        }
+    }
+
+    fn open_next_chunk(&mut self) -> io::Result<()> {
+        let chunk_len = STREAM_CHUNK_SIZE + TAG_SIZE;
+        let mut chunk = Vec::with_capacity(chunk_len);
+        chunk.extend(self.lookahead.take());
+        (&mut self.reader)
+            .take((chunk_len - chunk.len()) as u64)
+            .read_to_end(&mut chunk)?;
+
+        // Le bloc est le dernier si rien ne le suit
+        let mut next = [0u8; 1];
+        let last = chunk.len() < chunk_len || read_byte(&mut self.reader, &mut next)? == 0;
+        if !last {
+            self.lookahead = Some(next[0]);
+        }
+
+        let nonce = chunk_nonce(&self.nonce_prefix, self.counter, last);
+        let plaintext = self
+            .cipher
+            .decrypt(
+                self.key.expose(),
+                &nonce,
+                Payload {
+                    msg: &chunk,
+                    aad: &self.aad,
+                },
+            )
+            .map_err(|e| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("chunk {} failed to authenticate: {}", self.counter, e),
+                )
+            })?;
+        self.plaintext = Zeroizing::new(plaintext);
+        self.position = 0;
+        self.finished = last;
+        if !last {
+            self.counter = self.counter.checked_add(1).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "too many chunks in the file")
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for StreamDecryptor<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.position < self.plaintext.len() {
+                let len = buf.len().min(self.plaintext.len() - self.position);
+                buf[..len].copy_from_slice(&self.plaintext[self.position..self.position + len]);
+                self.position += len;
+                return Ok(len);
+            }
+            if self.finished {
+                return Ok(0);
+            }
+            self.open_next_chunk()?;
+        }
+    }
+}
+
+/// Copies a secret from `reader` to `writer` through a buffer wiped after use.
+pub fn copy_secret<R: Read, W: Write>(reader: &mut R, writer: &mut W) -> io::Result<u64> {
+    let mut buffer = Zeroizing::new(vec![0u8; STREAM_CHUNK_SIZE]);
+    let mut total = 0u64;
+    loop {
+        let len = match reader.read(&mut buffer) {
+            Ok(0) => return Ok(total),
+            Ok(len) => len,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buffer[..len])?;
+        total += len as u64;
+    }
+}
+
+fn read_byte<R: Read>(reader: &mut R, byte: &mut [u8; 1]) -> io::Result<usize> {
+    loop {
+        match reader.read(byte) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+fn chunk_nonce(prefix: &[u8], counter: u32, last: bool) -> Vec<u8> {
+    let mut nonce = Vec::with_capacity(prefix.len() + STREAM_NONCE_SUFFIX_SIZE);
+    nonce.extend_from_slice(prefix);
+    nonce.extend_from_slice(&counter.to_be_bytes());
+    nonce.push(last as u8);
+    nonce
+}
@@ -1,3 +1,5 @@
+use crate::utils::secret::SecretString;
+use crate::utils::vault::resolve_vault;
 use crate::LockerResult;
 use crate::SmartLockerError;
 use colored::Colorize;
@@ -5,9 +7,11 @@ use copypasta::{ClipboardContext, ClipboardProvider};
 use directories::UserDirs;
 use std::env;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
+use zeroize::Zeroizing;
 
 /// Vérifie et crée un répertoire s'il n'existe pas.
 ///
@@ -17,7 +21,7 @@ use std::process::Command;
 ///
 /// # Retourne
 ///
-/// * `LockerResult<()>` - Un résultat indiquant si l'opération a réussi ou non.
+/// * `LockerResult<bool>` - `true` si le répertoire a été créé, `false` s'il existait déjà.
 ///
 /// # Exemple
 ///
@@ -33,23 +37,230 @@ use std::process::Command;
 ///
 /// # Notes
 ///
-/// Si le répertoire existe déjà, la fonction ne fait rien.
+/// Si le répertoire existe déjà, la fonction ne fait rien. Sinon, il est créé
+/// accessible uniquement par son propriétaire (voir [`create_private_dir`]).
 /// Si une erreur se produit, elle retourne un `SmartLockerError`.
-pub fn ensure_dir_exists(path: &PathBuf) -> LockerResult<()> {
-    if !path.exists() {
-        fs::create_dir_all(path).map_err(|e| {
-            SmartLockerError::FileSystemError(format!("Error creating folder {:?}: {}", path, e))
+pub fn ensure_dir_exists(path: &PathBuf) -> LockerResult<bool> {
+    if path.exists() {
+        return Ok(false);
+    }
+    create_private_dir(path).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error creating folder {:?}: {}", path, e))
+    })?;
+    Ok(true)
+}
+
+/// Écrit un fichier de manière atomique.
+///
+/// Le contenu est d'abord écrit dans un fichier temporaire du même répertoire,
+/// synchronisé sur le disque, puis renommé vers sa destination, et le répertoire
+/// est synchronisé à son tour. Un lecteur voit donc soit l'ancien contenu, soit
+/// le nouveau, jamais un fichier tronqué, même après un arrêt brutal.
+///
+/// # Arguments
+///
+/// * `path` - Le chemin du fichier à écrire.
+/// * `content` - Le contenu à écrire.
+///
+/// # Retourne
+///
+/// * `LockerResult<bool>` - `true` si le répertoire a été créé, `false` s'il existait déjà.
+pub fn write_atomic(path: &Path, content: &[u8]) -> LockerResult<()> {
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| {
+            SmartLockerError::FileSystemError(format!("Invalid file path {:?}", path))
         })?;
-        println!("✅ Directory created: {:?}", path);
+    let tmp_path = unique_tmp_path(path, file_name);
+
+    let write_tmp = || -> std::io::Result<()> {
+        let mut file = create_private_file(&tmp_path)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+        fs::rename(&tmp_path, path)?;
+        sync_parent_dir(path)
+    };
+    write_tmp().map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        SmartLockerError::FileSystemError(format!("Error writing {:?}: {}", path, e))
+    })
+}
+
+/// Copie un fichier de manière atomique, comme [`write_atomic`].
+pub fn copy_atomic(from: &Path, to: &Path) -> LockerResult<()> {
+    let content = Zeroizing::new(fs::read(from).map_err(|e| {
+        SmartLockerError::FileSystemError(format!("Error reading {:?}: {}", from, e))
+    })?);
+    write_atomic(to, &content)
+}
+
+/// Retourne un chemin temporaire propre à cette écriture, à côté du fichier final.
+///
+/// Deux processus qui écrivent le même fichier n'utilisent jamais le même fichier temporaire.
+pub fn unique_tmp_path(path: &Path, file_name: &str) -> PathBuf {
+    path.with_file_name(format!(
+        "{}.{}.tmp",
+        file_name,
+        hex::encode(rand::random::<[u8; 8]>())
+    ))
+}
+
+/// Synchronise sur le disque le répertoire d'un fichier, pour qu'un renommage survive à un arrêt brutal.
+///
+/// Sans effet hors Unix, où un répertoire ne peut pas être ouvert ainsi.
+pub fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    #[cfg(unix)]
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        fs::File::open(parent)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Crée un répertoire et ses parents ; le répertoire final n'est accessible qu'à son propriétaire.
+///
+/// # Notes
+///
+/// Sous Unix, le répertoire reçoit les permissions `0700`.
+pub fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    fs::create_dir_all(path)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o700))?;
     }
     Ok(())
 }
 
-/// Retourne le chemin du répertoire `.locker`.
+/// Crée un fichier lisible uniquement par son propriétaire, pour y écrire un secret déchiffré.
+///
+/// # Arguments
+///
+/// * `path` - Le chemin du fichier à créer (remplacé s'il existe).
+///
+/// # Notes
+///
+/// Sous Unix, le fichier est créé avec les permissions `0600`.
+pub fn create_private_file(path: &Path) -> std::io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Écrit un secret déchiffré dans un fichier lisible uniquement par son propriétaire.
+///
+/// # Arguments
+///
+/// * `path` - Le chemin du fichier à écrire (remplacé s'il existe).
+/// * `content` - Le contenu du secret.
+///
+/// # Retourne
+///
+/// * `LockerResult<bool>` - `true` si le répertoire a été créé, `false` s'il existait déjà.
+pub fn write_private_file(path: &Path, content: &[u8]) -> LockerResult<()> {
+    let write_file = || -> std::io::Result<()> {
+        let mut file = create_private_file(path)?;
+        file.write_all(content)?;
+        file.sync_all()
+    };
+    write_file()
+        .map_err(|e| SmartLockerError::FileSystemError(format!("Error writing {:?}: {}", path, e)))
+}
+
+/// Retire les espaces, tabulations et retours à la ligne en début et en fin de secret.
+///
+/// # Exemple
+///
+/// ```rust
+/// use smart_locker::utils::toolbox::trim_whitespace;
+///
+/// assert_eq!(trim_whitespace(b"  my value\n"), b"my value");
+/// ```
+pub fn trim_whitespace(input: &[u8]) -> &[u8] {
+    let start = input
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(input.len());
+    let end = input
+        .iter()
+        .rposition(|byte| !byte.is_ascii_whitespace())
+        .map_or(start, |position| position + 1);
+    &input[start..end]
+}
+
+/// Retire un unique retour à la ligne final (`\n` ou `\r\n`), comme celui ajouté par `echo`.
+///
+/// # Exemple
+///
+/// ```rust
+/// use smart_locker::utils::toolbox::strip_trailing_newline;
+///
+/// assert_eq!(strip_trailing_newline(b"my value\r\n"), b"my value");
+/// assert_eq!(strip_trailing_newline(b"line\n\n"), b"line\n");
+/// ```
+pub fn strip_trailing_newline(input: &[u8]) -> &[u8] {
+    input
+        .strip_suffix(b"\r\n")
+        .or_else(|| input.strip_suffix(b"\n"))
+        .unwrap_or(input)
+}
+
+/// Convertit une durée comme `30d`, `12h` ou `2w` en secondes.
+///
+/// Unités acceptées : `s`, `m`, `h`, `d` et `w`. Un nombre seul est un nombre de jours.
+///
+/// # Exemple
+///
+/// ```rust
+/// use smart_locker::utils::toolbox::parse_duration;
+///
+/// assert_eq!(parse_duration("30d").unwrap(), 30 * 24 * 60 * 60);
+/// assert_eq!(parse_duration("90m").unwrap(), 90 * 60);
+/// assert_eq!(parse_duration("7").unwrap(), 7 * 24 * 60 * 60);
+/// assert!(parse_duration("soon").is_err());
+/// ```
+pub fn parse_duration(input: &str) -> LockerResult<u64> {
+    let input = input.trim();
+    let (number, unit) = match input.char_indices().last() {
+        Some((index, unit)) if unit.is_ascii_alphabetic() => (&input[..index], unit),
+        _ => (input, 'd'),
+    };
+    let seconds_per_unit = match unit.to_ascii_lowercase() {
+        's' => 1,
+        'm' => 60,
+        'h' => 60 * 60,
+        'd' => 24 * 60 * 60,
+        'w' => 7 * 24 * 60 * 60,
+        _ => 0,
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .filter(|_| seconds_per_unit > 0)
+        .and_then(|number| number.checked_mul(seconds_per_unit))
+        .ok_or_else(|| {
+            SmartLockerError::UnknownError(format!(
+                "Invalid duration '{}': use a number followed by s, m, h, d or w (e.g., 30d)",
+                input
+            ))
+        })
+}
+
+/// Retourne le chemin du vault sélectionné.
 ///
 /// # Retourne
 ///
-/// * `LockerResult<PathBuf>` - Le chemin du répertoire `.locker`.
+/// * `LockerResult<PathBuf>` - Le répertoire du vault.
 ///
 /// # Exemple
 ///
@@ -60,13 +271,20 @@ pub fn ensure_dir_exists(path: &PathBuf) -> LockerResult<()> {
 /// println!("Locker directory: {:?}", locker_dir);
 /// ```
 ///
-/// Ce code retourne le chemin du répertoire `.locker`, qui est soit défini par une variable d'environnement, soit par défaut dans le répertoire utilisateur.
+/// # Notes
+///
+/// Voir [`resolve_vault`] pour l'ordre de recherche et pour savoir quel vault a été choisi.
+pub fn get_locker_dir() -> LockerResult<PathBuf> {
+    Ok(resolve_vault()?.path)
+}
+
+/// Retourne le chemin du répertoire `.locker` personnel, qui contient aussi le registre des vaults.
 ///
 /// # Notes
 ///
 /// Si une variable d'environnement `SMART_LOCKER_TEST_DIR` ou `SMART_LOCKER_TEST_DIR_*` est définie, elle sera utilisée comme chemin.
 /// Sinon, le répertoire par défaut est `~/.locker`.
-pub fn get_locker_dir() -> LockerResult<PathBuf> {
+pub fn home_locker_dir() -> LockerResult<PathBuf> {
     // Rechercher une variable d'environnement spécifique au test
     if let Some((_, value)) = env::vars()
         .filter(|(key, _)| key.starts_with("SMART_LOCKER_TEST_DIR_"))
@@ -86,6 +304,7 @@ pub fn get_locker_dir() -> LockerResult<PathBuf> {
         Ok(user_dirs.home_dir().join(".locker"))
     }
 }
+
 /// Vérifie si le fichier donné est un secret valide avec l'extension `.slock`.
 ///
 /// # Arguments
@@ -149,7 +368,7 @@ pub fn is_this_secret(file_path: &Path, silent: bool) -> (bool, Option<String>)
 ///
 /// # Arguments
 ///
-/// * `content` - La chaîne de caractères à copier dans le presse-papiers.
+/// * `secret` - Le secret à copier dans le presse-papiers.
 ///
 /// # Retourne
 ///
@@ -159,7 +378,8 @@ pub fn is_this_secret(file_path: &Path, silent: bool) -> (bool, Option<String>)
 ///
 /// Cette fonction est compatible avec Windows, Linux, et WSL.
 /// Si l'accès au presse-papiers échoue, une erreur est retournée avec un message explicatif.
-pub fn copy_to_clipboard(content: &str) -> Result<(), String> {
+pub fn copy_to_clipboard(secret: &SecretString) -> Result<(), String> {
+    let content = secret.expose();
     println!("Attempting to copy to clipboard...");
 
     if cfg!(target_os = "windows") {
@@ -0,0 +1,286 @@
+use crate::utils::toolbox::{home_locker_dir, write_atomic};
+use crate::LockerResult;
+use crate::SmartLockerError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const VAULT_REGISTRY_FILE: &str = "vaults.json"; // Registre des vaults nommés, dans `~/.locker`
+pub const VAULT_ENV: &str = "SMART_LOCKER_VAULT"; // Vault sélectionné par variable d'environnement
+pub const DEFAULT_VAULT_NAME: &str = "default"; // Nom réservé du vault `~/.locker`
+pub const LOCAL_VAULT_DIR: &str = ".smart-locker"; // Vault d'un projet, trouvé en remontant les dossiers
+const VAULTS_DIR: &str = "vaults"; // Emplacement par défaut des nouveaux vaults
+
+/// The vault selected for the rest of the process.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VaultSelection {
+    /// A vault registered under this name (`--vault`, `SMART_LOCKER_VAULT`).
+    Named(String),
+    /// A locker directory given explicitly (`--locker-dir`).
+    Path(PathBuf),
+}
+
+static SELECTED_VAULT: Mutex<Option<VaultSelection>> = Mutex::new(None);
+
+/// Selects the vault used by every command for the rest of the process.
+///
+/// The CLI calls this from `--vault` / `--locker-dir`. See [`resolve_vault`]
+/// for what is used without a selection.
+pub fn select_vault(selection: VaultSelection) {
+    *SELECTED_VAULT.lock().unwrap() = Some(selection);
+}
+
+/// Where the vault in use comes from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VaultSource {
+    /// The `--locker-dir` option.
+    LockerDirOption,
+    /// The `--vault` option.
+    VaultOption,
+    /// The `SMART_LOCKER_VAULT` environment variable.
+    VaultEnv,
+    /// A `.smart-locker/` directory found in the working directory or one of its parents.
+    Local,
+    /// The default vault of the registry.
+    RegistryDefault,
+    /// The home locker, `~/.locker`.
+    Home,
+}
+
+impl fmt::Display for VaultSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VaultSource::LockerDirOption => "--locker-dir",
+            VaultSource::VaultOption => "--vault",
+            VaultSource::VaultEnv => VAULT_ENV,
+            VaultSource::Local => "project directory",
+            VaultSource::RegistryDefault => "default vault",
+            VaultSource::Home => "home directory",
+        })
+    }
+}
+
+/// The vault picked by [`resolve_vault`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolvedVault {
+    /// Directory of the locker.
+    pub path: PathBuf,
+    /// Name of the vault in the registry, if it has one.
+    pub name: Option<String>,
+    /// Why this vault was picked.
+    pub source: VaultSource,
+}
+
+impl fmt::Display for ResolvedVault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = &self.name {
+            write!(f, "'{}' ", name)?;
+        }
+        write!(f, "{:?} (from {})", self.path, self.source)
+    }
+}
+
+/// Finds the vault to use, the way git finds `.git`.
+///
+/// The vault is picked in this order:
+/// 1. `--locker-dir` or `--vault` (see [`select_vault`]);
+/// 2. the `SMART_LOCKER_VAULT` environment variable;
+/// 3. a `.smart-locker/` directory in the working directory or one of its parents;
+/// 4. the default vault of the registry (`smart-locker vault default`);
+/// 5. the home locker returned by [`home_locker_dir`].
+pub fn resolve_vault() -> LockerResult<ResolvedVault> {
+    let home_dir = home_locker_dir()?;
+    let named = |name: String, source: VaultSource| -> LockerResult<ResolvedVault> {
+        let path = VaultRegistry::load(&home_dir)?.path_of(&name, &home_dir)?;
+        Ok(ResolvedVault {
+            path,
+            name: Some(name),
+            source,
+        })
+    };
+
+    match SELECTED_VAULT.lock().unwrap().clone() {
+        Some(VaultSelection::Path(path)) => {
+            return Ok(ResolvedVault {
+                path,
+                name: None,
+                source: VaultSource::LockerDirOption,
+            })
+        }
+        Some(VaultSelection::Named(name)) => return named(name, VaultSource::VaultOption),
+        None => {}
+    }
+    if let Some(name) = env::var(VAULT_ENV).ok().filter(|name| !name.is_empty()) {
+        return named(name, VaultSource::VaultEnv);
+    }
+    if let Some(path) = env::current_dir()
+        .ok()
+        .and_then(|dir| find_local_vault(&dir))
+    {
+        return Ok(ResolvedVault {
+            path,
+            name: None,
+            source: VaultSource::Local,
+        });
+    }
+    let registry = VaultRegistry::load(&home_dir)?;
+    match &registry.default {
+        Some(name) => named(name.clone(), VaultSource::RegistryDefault),
+        None => Ok(ResolvedVault {
+            path: home_dir,
+            name: Some(DEFAULT_VAULT_NAME.to_string()),
+            source: VaultSource::Home,
+        }),
+    }
+}
+
+/// Looks for a `.smart-locker/` directory in `start` and its parents.
+pub fn find_local_vault(start: &Path) -> Option<PathBuf> {
+    start
+        .ancestors()
+        .map(|dir| dir.join(LOCAL_VAULT_DIR))
+        .find(|candidate| candidate.is_dir())
+}
+
+/// The registry of named vaults: a name for each locker directory, and a default.
+///
+/// It is stored in `vaults.json` in the home locker (`~/.locker`), which is
+/// always available as the vault named `default`.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct VaultRegistry {
+    /// Vault used when none is selected; `None` means `default`.
+    #[serde(default)]
+    pub default: Option<String>,
+    /// Locker directory of each named vault.
+    #[serde(default)]
+    pub vaults: BTreeMap<String, PathBuf>,
+}
+
+impl VaultRegistry {
+    /// Reads the registry kept in `home_dir`, empty if it does not exist yet.
+    pub fn load(home_dir: &Path) -> LockerResult<Self> {
+        let registry_path = home_dir.join(VAULT_REGISTRY_FILE);
+        if !registry_path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read(&registry_path).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Unable to read {:?}: {}", registry_path, e))
+        })?;
+        serde_json::from_slice(&content).map_err(|e| {
+            SmartLockerError::FileSystemError(format!("Error parsing {:?}: {}", registry_path, e))
+        })
+    }
+
+    /// Writes the registry to `home_dir` atomically.
+    pub fn save(&self, home_dir: &Path) -> LockerResult<()> {
+        fs::create_dir_all(home_dir).map_err(|e| {
+            SmartLockerError::FileSystemError(format!(
+                "Error creating folder {:?}: {}",
+                home_dir, e
+            ))
+        })?;
+        let content = serde_json::to_string_pretty(self).map_err(|e| {
+            SmartLockerError::FileSystemError(format!(
+                "Error serializing the vault registry: {}",
+                e
+            ))
+        })?;
+        write_atomic(&home_dir.join(VAULT_REGISTRY_FILE), content.as_bytes())
+    }
+
+    /// Returns the locker directory of a vault; `default` is the home locker.
+    pub fn path_of(&self, name: &str, home_dir: &Path) -> LockerResult<PathBuf> {
+        if name == DEFAULT_VAULT_NAME {
+            return Ok(home_dir.to_path_buf());
+        }
+        self.vaults.get(name).cloned().ok_or_else(|| {
+            SmartLockerError::InitializationError(format!(
+                "Unknown vault '{}'. Run `smart-locker vault list` to see the registered vaults.",
+                name
+            ))
+        })
+    }
+
+    /// Returns the name of the default vault.
+    pub fn default_name(&self) -> &str {
+        self.default.as_deref().unwrap_or(DEFAULT_VAULT_NAME)
+    }
+
+    /// Registers a vault. Without a path, it is placed in `vaults/<name>` under `home_dir`.
+    pub fn add(
+        &mut self,
+        name: &str,
+        path: Option<&Path>,
+        home_dir: &Path,
+    ) -> LockerResult<PathBuf> {
+        validate_vault_name(name)?;
+        if name == DEFAULT_VAULT_NAME || self.vaults.contains_key(name) {
+            return Err(SmartLockerError::InitializationError(format!(
+                "A vault named '{}' already exists",
+                name
+            )));
+        }
+        let path = match path {
+            Some(path) if path.is_relative() => env::current_dir()
+                .map_err(|e| {
+                    SmartLockerError::FileSystemError(format!(
+                        "Unable to get current directory: {}",
+                        e
+                    ))
+                })?
+                .join(path),
+            Some(path) => path.to_path_buf(),
+            None => home_dir.join(VAULTS_DIR).join(name),
+        };
+        self.vaults.insert(name.to_string(), path.clone());
+        Ok(path)
+    }
+
+    /// Unregisters a vault, returning its directory. The files are left in place.
+    pub fn remove(&mut self, name: &str) -> LockerResult<PathBuf> {
+        if name == DEFAULT_VAULT_NAME {
+            return Err(SmartLockerError::InitializationError(
+                "The default vault cannot be removed".to_string(),
+            ));
+        }
+        let path = self.vaults.remove(name).ok_or_else(|| {
+            SmartLockerError::InitializationError(format!("Unknown vault '{}'", name))
+        })?;
+        if self.default.as_deref() == Some(name) {
+            self.default = None;
+        }
+        Ok(path)
+    }
+
+    /// Makes a vault the default one.
+    pub fn set_default(&mut self, name: &str) -> LockerResult<()> {
+        if name != DEFAULT_VAULT_NAME && !self.vaults.contains_key(name) {
+            return Err(SmartLockerError::InitializationError(format!(
+                "Unknown vault '{}'",
+                name
+            )));
+        }
+        self.default = (name != DEFAULT_VAULT_NAME).then(|| name.to_string());
+        Ok(())
+    }
+}
+
+/// Checks that a vault name is made of letters, digits, `-` and `_`.
+pub fn validate_vault_name(name: &str) -> LockerResult<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(SmartLockerError::InitializationError(format!(
+            "Invalid vault name '{}': use letters, digits, '-' and '_'",
+            name
+        )))
+    }
+}